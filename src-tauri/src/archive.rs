@@ -0,0 +1,552 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_std::fs;
+use chrono::prelude::*;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::Clock,
+    history::DaySummary,
+    repair,
+    schema::{self, Versioned},
+    storage,
+    timecard::{self, EventLog},
+};
+
+// Every logged day from one month, packed into a single compressed file
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MonthArchive {
+    pub days: BTreeMap<NaiveDate, ArchivedDay>,
+}
+
+impl Versioned for MonthArchive {
+    const MIGRATIONS: &'static [schema::Migration] = &[schema::unversioned];
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedDay {
+    pub summary: DaySummary,
+    // Dropped once the day is older than the raw event retention
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<ArchivedLog>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedLog(#[serde(with = "schema::nested")] pub Box<EventLog>);
+
+pub fn archive_dir(logs_dir: &Path) -> PathBuf {
+    logs_dir.join("archive")
+}
+
+fn archive_file_for(logs_dir: &Path, year: i32, month: u32) -> PathBuf {
+    archive_dir(logs_dir).join(format!("{}-{}.json.gz", year, month))
+}
+
+// The first day of the month an archive is for
+fn month_for_archive_file(archive_file: &Path) -> Option<NaiveDate> {
+    let name = archive_file.file_name()?.to_str()?;
+    let month = name.strip_suffix(".json.gz")?;
+
+    NaiveDate::parse_from_str(&format!("{}-1", month), "%Y-%m-%d").ok()
+}
+
+pub async fn read_month(
+    logs_dir: &Path,
+    year: i32,
+    month: u32,
+) -> Result<Option<MonthArchive>, Box<dyn Error>> {
+    let archive_file = archive_file_for(logs_dir, year, month);
+
+    if !archive_file.exists() {
+        return Ok(None);
+    }
+
+    let compressed = fs::read(&archive_file).await?;
+
+    let mut json = Vec::new();
+    GzDecoder::new(&compressed[..]).read_to_end(&mut json)?;
+
+    Ok(Some(schema::from_slice(&json)?))
+}
+
+async fn write_month(
+    logs_dir: &Path,
+    year: i32,
+    month: u32,
+    archive: &MonthArchive,
+) -> Result<(), Box<dyn Error>> {
+    let archive_file = archive_file_for(logs_dir, year, month);
+
+    if archive.days.is_empty() {
+        fs::remove_file(&archive_file).await?;
+        return Ok(());
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&schema::to_vec(archive)?)?;
+
+    storage::write_atomic(archive_file.as_path().into(), &encoder.finish()?).await
+}
+
+// How far an archiving run got, so the next one can skip what's already done
+pub struct ArchiveRun {
+    month: NaiveDate,
+    dropped_before: Option<NaiveDate>,
+    // Logs that couldn't be archived
+    pub warnings: Vec<String>,
+}
+
+impl ArchiveRun {
+    // Logs are only packed up after their month is over, so there's nothing new until the next one
+    pub fn is_current(&self, today: NaiveDate) -> bool {
+        self.month == today.with_day(1).unwrap()
+    }
+}
+
+// Packs the logs from months before the current one into monthly archives, then drops the raw
+// events from archived days older than `retention_days`, keeping just their summaries. Logs that
+// can't be read are left out, with a warning for each. Archives that were already past the
+// `previous` run's cutoff aren't opened again.
+pub async fn archive_old_logs(
+    logs_dir: &Path,
+    today: NaiveDate,
+    retention_days: Option<u32>,
+    previous: Option<&ArchiveRun>,
+    clock: Arc<dyn Clock>,
+) -> Result<ArchiveRun, Box<dyn Error>> {
+    let mut warnings = Vec::new();
+    let month_start = today.with_day(1).unwrap();
+    let cutoff = retention_days.map(|days| today - chrono::Days::new(days.into()));
+    let mut months: BTreeMap<(i32, u32), Vec<NaiveDate>> = BTreeMap::new();
+
+    for entry in std::fs::read_dir(logs_dir)? {
+        match timecard::date_for_log_file(&entry?.path()) {
+            Some(date) if date < month_start => {
+                months
+                    .entry((date.year(), date.month()))
+                    .or_default()
+                    .push(date);
+            }
+            _ => {}
+        }
+    }
+
+    fs::create_dir_all(archive_dir(logs_dir)).await?;
+
+    for ((year, month), dates) in months {
+        let mut archive = read_month(logs_dir, year, month).await?.unwrap_or_default();
+        let mut archived = Vec::new();
+
+        for date in dates {
+            let log_file = timecard::log_file_for_date(logs_dir, date);
+
+            let loaded = EventLog::load(log_file.clone().into(), clock.clone())
+                .await
+                .map_err(|err| err.to_string());
+
+            let mut event_log = match loaded {
+                Ok(event_log) => event_log,
+                Err(err) => {
+                    warnings.push(skip_unreadable(logs_dir, &log_file, err, &*clock).await);
+                    continue;
+                }
+            };
+
+            // Most of a day's events are activity updates that didn't change anything
+            event_log.prune_activity();
+
+            archive.days.insert(
+                date,
+                ArchivedDay {
+                    summary: DaySummary::from_event_log(&event_log),
+                    log: Some(ArchivedLog(Box::new(event_log))),
+                },
+            );
+            archived.push(date);
+        }
+
+        if archived.is_empty() {
+            continue;
+        }
+
+        if let Some(cutoff) = cutoff {
+            drop_events(&mut archive, cutoff);
+        }
+
+        write_month(logs_dir, year, month, &archive).await?;
+
+        // Only remove the logs once they're safely in the archive
+        for date in archived {
+            fs::remove_file(timecard::log_file_for_date(logs_dir, date)).await?;
+        }
+    }
+
+    if let Some(cutoff) = cutoff {
+        let since = previous.and_then(|run| run.dropped_before);
+        drop_events_before(logs_dir, cutoff, since).await?;
+    }
+
+    Ok(ArchiveRun {
+        month: month_start,
+        dropped_before: cutoff,
+        warnings,
+    })
+}
+
+// Moves a log that can't be read out of the way so it doesn't hold up archiving every time, unless
+// it's just from a newer version of the app
+async fn skip_unreadable(
+    logs_dir: &Path,
+    log_file: &Path,
+    err: String,
+    clock: &dyn Clock,
+) -> String {
+    let log_file: async_std::path::PathBuf = log_file.into();
+
+    if let Err(err) = repair::check_version(&log_file).await {
+        return format!("couldn't archive {:?}: {}", log_file, err);
+    }
+
+    match repair::quarantine(&log_file, &repair::quarantine_dir(logs_dir), clock).await {
+        Ok(moved) => format!(
+            "couldn't archive {:?}, so it was quarantined as {:?}: {}",
+            log_file, moved, err
+        ),
+        Err(quarantine_err) => format!(
+            "couldn't archive {:?}: {}, and couldn't quarantine it: {}",
+            log_file, err, quarantine_err
+        ),
+    }
+}
+
+// Drops the events from archives with days before `cutoff`, skipping months that were entirely
+// before `since` since an earlier cutoff already covered them
+async fn drop_events_before(
+    logs_dir: &Path,
+    cutoff: NaiveDate,
+    since: Option<NaiveDate>,
+) -> Result<(), Box<dyn Error>> {
+    for entry in std::fs::read_dir(archive_dir(logs_dir))? {
+        let Some(month) = month_for_archive_file(&entry?.path()) else {
+            continue;
+        };
+
+        let month_end = month + chrono::Months::new(1);
+
+        if month >= cutoff || since.is_some_and(|since| month_end <= since) {
+            continue;
+        }
+
+        let Some(mut archive) = read_month(logs_dir, month.year(), month.month()).await? else {
+            continue;
+        };
+
+        if drop_events(&mut archive, cutoff) {
+            write_month(logs_dir, month.year(), month.month(), &archive).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Returns whether any days had events to drop
+fn drop_events(archive: &mut MonthArchive, cutoff: NaiveDate) -> bool {
+    let mut dropped = false;
+
+    for (_, day) in archive.days.range_mut(..cutoff) {
+        dropped |= day.log.take().is_some();
+    }
+
+    dropped
+}
+
+pub async fn is_archived(logs_dir: &Path, date: NaiveDate) -> Result<bool, Box<dyn Error>> {
+    let archive = read_month(logs_dir, date.year(), date.month()).await?;
+
+    Ok(archive.is_some_and(|archive| archive.days.contains_key(&date)))
+}
+
+// Moves an archived day back out into its own log so it can be changed, returning false if the
+// day isn't archived at all
+pub async fn restore_day(
+    logs_dir: &Path,
+    date: NaiveDate,
+    clock: Arc<dyn Clock>,
+) -> Result<bool, Box<dyn Error>> {
+    let Some(mut archive) = read_month(logs_dir, date.year(), date.month()).await? else {
+        return Ok(false);
+    };
+
+    let Some(day) = archive.days.remove(&date) else {
+        return Ok(false);
+    };
+
+    let ArchivedLog(mut event_log) = day.log.ok_or_else(|| {
+        format!(
+            "{} was archived without its events, so it can't be changed",
+            date
+        )
+    })?;
+
+    event_log.relocate(timecard::log_file_for_date(logs_dir, date).into(), clock);
+    event_log.compact().await?;
+
+    write_month(logs_dir, date.year(), date.month(), &archive).await?;
+
+    Ok(true)
+}
+
+pub async fn first_archived_day(logs_dir: &Path) -> Result<Option<NaiveDate>, Box<dyn Error>> {
+    let archive_dir = archive_dir(logs_dir);
+
+    if !archive_dir.exists() {
+        return Ok(None);
+    }
+
+    let mut first_month = None;
+
+    for entry in std::fs::read_dir(archive_dir)? {
+        if let Some(month) = month_for_archive_file(&entry?.path()) {
+            first_month = Some(first_month.map_or(month, |first: NaiveDate| first.min(month)));
+        }
+    }
+
+    let Some(month) = first_month else {
+        return Ok(None);
+    };
+
+    Ok(read_month(logs_dir, month.year(), month.month())
+        .await?
+        .and_then(|archive| archive.days.keys().next().copied()))
+}
+
+// Reads day summaries out of the archives, holding on to the last month read since days are
+// usually read in order
+#[derive(Default)]
+pub struct Archives {
+    month: Option<((i32, u32), Option<MonthArchive>)>,
+}
+
+impl Archives {
+    pub async fn summary(
+        &mut self,
+        logs_dir: &Path,
+        date: NaiveDate,
+    ) -> Result<Option<DaySummary>, Box<dyn Error>> {
+        let key = (date.year(), date.month());
+
+        if !matches!(&self.month, Some((month, _)) if *month == key) {
+            self.month = Some((key, read_month(logs_dir, key.0, key.1).await?));
+        }
+
+        Ok(self
+            .month
+            .as_ref()
+            .and_then(|(_, archive)| archive.as_ref())
+            .and_then(|archive| archive.days.get(&date))
+            .map(|day| day.summary.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::history;
+    use crate::settings::Schedule;
+    use crate::test_util::{event_log_in, hours, local, temp_dir};
+    use crate::timecard::{ClockType, DayStatus, Event};
+
+    async fn write_day(logs_dir: &Path, clock: &Arc<FakeClock>, date: NaiveDate, hours: i64) {
+        clock.set(
+            Local
+                .from_local_datetime(&date.and_hms_opt(9, 0, 0).unwrap())
+                .unwrap(),
+        );
+
+        let mut event_log = event_log_in(logs_dir, clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.advance(chrono::Duration::hours(hours));
+        event_log.add_event(Event::clock_out(event_log.now(), ClockType::Day));
+
+        event_log.compact().await.unwrap();
+    }
+
+    #[test]
+    fn old_logs_are_archived_and_still_readable() {
+        async_std::task::block_on(async {
+            let logs_dir = temp_dir("archive");
+
+            let clock = FakeClock::new(Local.with_ymd_and_hms(2023, 10, 2, 9, 0, 0).unwrap());
+            let date = |month, day| NaiveDate::from_ymd_opt(2023, month, day).unwrap();
+
+            write_day(&logs_dir, &clock, date(10, 2), 8).await;
+            write_day(&logs_dir, &clock, date(10, 3), 6).await;
+            write_day(&logs_dir, &clock, date(11, 1), 7).await;
+
+            let today = date(11, 15);
+            archive_old_logs(&logs_dir, today, None, None, clock.clone())
+                .await
+                .unwrap();
+
+            // Only the previous month's logs get packed away
+            assert!(!timecard::log_file_for_date(&logs_dir, date(10, 2)).exists());
+            assert!(!timecard::log_file_for_date(&logs_dir, date(10, 3)).exists());
+            assert!(timecard::log_file_for_date(&logs_dir, date(11, 1)).exists());
+            assert!(archive_file_for(&logs_dir, 2023, 10).exists());
+
+            clock.set(local(15, 9, 0));
+            let mut current = event_log_in(&logs_dir, &clock);
+
+            let schedule = Schedule::weekdays(hours(8));
+            let range = history::load_range(&logs_dir, date(10, 1), today, &current, &schedule)
+                .await
+                .unwrap();
+            let dates: Vec<_> = range.days.iter().map(|day| day.date).collect();
+            assert_eq!(dates, vec![date(10, 2), date(10, 3), date(11, 1), today]);
+            assert_eq!(range.days[1].elapsed.work_time, hours(6));
+
+            assert_eq!(
+                history::first_logged_day(&logs_dir).await.unwrap(),
+                Some(date(10, 2))
+            );
+
+            // Changing an archived day moves it back out into its own log
+            history::set_day_note(&logs_dir, date(10, 3), Some("moved".into()), &mut current)
+                .await
+                .unwrap();
+            assert!(timecard::log_file_for_date(&logs_dir, date(10, 3)).exists());
+
+            let archive = read_month(&logs_dir, 2023, 10).await.unwrap().unwrap();
+            assert_eq!(
+                archive.days.keys().copied().collect::<Vec<_>>(),
+                vec![date(10, 2)]
+            );
+
+            // Taking an archived day off is left to the schedule instead of restoring its log
+            history::set_day_status(&logs_dir, date(10, 2), DayStatus::Sick, &mut current)
+                .await
+                .unwrap();
+            assert!(!timecard::log_file_for_date(&logs_dir, date(10, 2)).exists());
+
+            let mut schedule = schedule;
+            schedule.set_status(date(10, 2), DayStatus::Sick);
+
+            let range =
+                history::load_range(&logs_dir, date(10, 2), date(10, 3), &current, &schedule)
+                    .await
+                    .unwrap();
+            assert_eq!(range.days[0].status, DayStatus::Sick);
+            assert_eq!(range.days[1].note.as_deref(), Some("moved"));
+            assert_eq!(range.days[1].elapsed.work_time, hours(6));
+
+            std::fs::remove_dir_all(&logs_dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn unreadable_logs_are_quarantined() {
+        async_std::task::block_on(async {
+            let logs_dir = temp_dir("archive-unreadable");
+
+            let clock = FakeClock::new(Local.with_ymd_and_hms(2023, 10, 2, 9, 0, 0).unwrap());
+            let date = |month, day| NaiveDate::from_ymd_opt(2023, month, day).unwrap();
+
+            write_day(&logs_dir, &clock, date(9, 29), 8).await;
+            write_day(&logs_dir, &clock, date(10, 2), 8).await;
+            std::fs::write(
+                timecard::log_file_for_date(&logs_dir, date(10, 3)),
+                "{\"ini",
+            )
+            .unwrap();
+
+            let warnings = archive_old_logs(&logs_dir, date(11, 15), None, None, clock.clone())
+                .await
+                .unwrap()
+                .warnings;
+            assert_eq!(warnings.len(), 1);
+
+            // Everything else still gets archived, and the bad log doesn't come up again
+            let october = read_month(&logs_dir, 2023, 10).await.unwrap().unwrap();
+            assert_eq!(
+                october.days.keys().copied().collect::<Vec<_>>(),
+                vec![date(10, 2)]
+            );
+            assert!(read_month(&logs_dir, 2023, 9).await.unwrap().is_some());
+            assert!(!timecard::log_file_for_date(&logs_dir, date(10, 3)).exists());
+            assert_eq!(
+                repair::list_quarantined(&repair::quarantine_dir(&logs_dir))
+                    .await
+                    .unwrap()
+                    .len(),
+                1
+            );
+
+            let warnings = archive_old_logs(&logs_dir, date(11, 15), None, None, clock.clone())
+                .await
+                .unwrap()
+                .warnings;
+            assert!(warnings.is_empty());
+
+            std::fs::remove_dir_all(&logs_dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn retention_drops_raw_events() {
+        async_std::task::block_on(async {
+            let logs_dir = temp_dir("archive-retention");
+
+            let clock = FakeClock::new(Local.with_ymd_and_hms(2023, 8, 1, 9, 0, 0).unwrap());
+            let date = |month, day| NaiveDate::from_ymd_opt(2023, month, day).unwrap();
+
+            write_day(&logs_dir, &clock, date(8, 1), 8).await;
+            write_day(&logs_dir, &clock, date(10, 30), 8).await;
+
+            let today = date(11, 15);
+            let run = archive_old_logs(&logs_dir, today, Some(30), None, clock.clone())
+                .await
+                .unwrap();
+            assert!(run.is_current(date(11, 30)));
+            assert!(!run.is_current(date(12, 1)));
+
+            let august = read_month(&logs_dir, 2023, 8).await.unwrap().unwrap();
+            assert!(august.days[&date(8, 1)].log.is_none());
+
+            let october = read_month(&logs_dir, 2023, 10).await.unwrap().unwrap();
+            assert!(october.days[&date(10, 30)].log.is_some());
+
+            // The summary outlives the events, but the day can't be changed anymore
+            let mut archives = Archives::default();
+            let summary = archives.summary(&logs_dir, date(8, 1)).await.unwrap();
+            assert_eq!(summary.unwrap().elapsed.work_time, hours(8));
+            assert!(restore_day(&logs_dir, date(8, 1), clock.clone())
+                .await
+                .is_err());
+            assert!(!restore_day(&logs_dir, date(8, 2), clock.clone())
+                .await
+                .unwrap());
+
+            // A month later only October has newly expired days, so August isn't opened again
+            std::fs::write(archive_file_for(&logs_dir, 2023, 8), "not an archive").unwrap();
+            archive_old_logs(&logs_dir, date(12, 15), Some(30), Some(&run), clock.clone())
+                .await
+                .unwrap();
+
+            let october = read_month(&logs_dir, 2023, 10).await.unwrap().unwrap();
+            assert!(october.days[&date(10, 30)].log.is_none());
+
+            std::fs::remove_dir_all(&logs_dir).unwrap();
+        });
+    }
+}
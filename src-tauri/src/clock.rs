@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use chrono::prelude::*;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+#[cfg(test)]
+pub struct FakeClock {
+    now: std::sync::Mutex<DateTime<Local>>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(now: DateTime<Local>) -> Arc<FakeClock> {
+        Arc::new(FakeClock {
+            now: std::sync::Mutex::new(now),
+        })
+    }
+
+    pub fn set(&self, now: DateTime<Local>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, duration: chrono::Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.now.lock().unwrap()
+    }
+}
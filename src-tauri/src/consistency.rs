@@ -0,0 +1,368 @@
+use chrono::prelude::*;
+use serde::Serialize;
+
+use crate::timecard::{ClockType, Event, EventLog, State};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all_fields = "camelCase")]
+pub enum Diagnostic {
+    ClockInWhileClockedIn {
+        time: DateTime<FixedOffset>,
+        clock: ClockType,
+    },
+    ClockOutWithoutClockIn {
+        time: DateTime<FixedOffset>,
+        clock: ClockType,
+    },
+    BreakDuringLunch {
+        time: DateTime<FixedOffset>,
+    },
+    LunchDuringBreak {
+        time: DateTime<FixedOffset>,
+    },
+    EventOutsideDate {
+        time: DateTime<FixedOffset>,
+    },
+    IdleResolvedWithoutSpan {
+        time: DateTime<FixedOffset>,
+    },
+    TaskTimeExceedsWorkTime {
+        task_time: std::time::Duration,
+        work_time: std::time::Duration,
+    },
+    StateMismatch {
+        field: String,
+    },
+}
+
+pub fn check(event_log: &EventLog) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    // Walk the events the same way `timecard::replay` does, checking each one against the
+    // state it gets applied to
+    let mut state = event_log.initial_state().clone();
+
+    for event in event_log.events() {
+        if !event_log.contains(event.time()) {
+            diagnostics.push(Diagnostic::EventOutsideDate { time: event.time() });
+        }
+
+        check_event(&state, event, &mut diagnostics);
+        state.apply(event);
+    }
+
+    // Manual time that doesn't count as work was never meant to fit inside working time
+    let non_work_manual_time: std::time::Duration = event_log
+        .events()
+        .iter()
+        .filter_map(|event| match event {
+            Event::ManualTime {
+                duration,
+                counts_as_work: false,
+                ..
+            } => Some(*duration),
+            _ => None,
+        })
+        .sum();
+
+    // Anything still running counts up to the log's current time, like the elapsed totals do
+    let (date, day_start, now) = (event_log.date(), event_log.day_start(), event_log.now());
+
+    let task_time = state
+        .tasks
+        .elapsed_for_date(date, day_start, now)
+        .values()
+        .sum::<std::time::Duration>()
+        .saturating_sub(non_work_manual_time);
+    let work_time = state.working.elapsed_for_date(date, day_start, now);
+
+    if task_time > work_time {
+        diagnostics.push(Diagnostic::TaskTimeExceedsWorkTime {
+            task_time,
+            work_time,
+        });
+    }
+
+    compare_states(&state, event_log.current_state(), &mut diagnostics);
+
+    diagnostics
+}
+
+fn check_event(state: &State, event: &Event, diagnostics: &mut Vec<Diagnostic>) {
+    match event {
+        Event::ClockIn { time, clock, .. } => {
+            if clock_open(state, clock) {
+                diagnostics.push(Diagnostic::ClockInWhileClockedIn {
+                    time: *time,
+                    clock: clock.clone(),
+                });
+            }
+
+            if *clock == ClockType::Break && state.on_lunch.active() {
+                diagnostics.push(Diagnostic::BreakDuringLunch { time: *time });
+            }
+
+            if *clock == ClockType::Lunch && state.on_break.active() {
+                diagnostics.push(Diagnostic::LunchDuringBreak { time: *time });
+            }
+        }
+        Event::ClockOut { time, clock, .. } => {
+            if !clock_open(state, clock) {
+                diagnostics.push(Diagnostic::ClockOutWithoutClockIn {
+                    time: *time,
+                    clock: clock.clone(),
+                });
+            }
+        }
+        Event::ResolveIdle { time, span, .. } => {
+            if !state.pending_idle.contains(span) {
+                diagnostics.push(Diagnostic::IdleResolvedWithoutSpan { time: *time });
+            }
+        }
+        Event::Active { .. }
+        | Event::Idle { .. }
+        | Event::Tasks { .. }
+        | Event::ManualTime { .. } => {}
+    }
+}
+
+fn clock_open(state: &State, clock: &ClockType) -> bool {
+    match clock {
+        ClockType::Day => state.working.active(),
+        ClockType::Break => state.on_break.active(),
+        ClockType::Lunch => state.on_lunch.active(),
+        ClockType::Category(category) => state
+            .categories
+            .get(&category.name)
+            .is_some_and(|category| category.time.active()),
+    }
+}
+
+fn compare_states(replayed: &State, persisted: &State, diagnostics: &mut Vec<Diagnostic>) {
+    let mut mismatch = |field: &str| {
+        diagnostics.push(Diagnostic::StateMismatch {
+            field: field.to_owned(),
+        })
+    };
+
+    if replayed.working != persisted.working {
+        mismatch("working");
+    }
+    if replayed.on_break != persisted.on_break {
+        mismatch("onBreak");
+    }
+    if replayed.on_lunch != persisted.on_lunch {
+        mismatch("onLunch");
+    }
+    if replayed.idle_work != persisted.idle_work {
+        mismatch("idleWork");
+    }
+    if replayed.tasks != persisted.tasks {
+        mismatch("tasks");
+    }
+    if replayed.pending_idle != persisted.pending_idle {
+        mismatch("pendingIdle");
+    }
+    if replayed.categories != persisted.categories {
+        mismatch("categories");
+    }
+
+    // The active time gets refreshed without an event, so only check whether it's set
+    if replayed.active_until.is_some() != persisted.active_until.is_some() {
+        mismatch("activeUntil");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::test_util::{hours, local, task, time};
+    use crate::timecard::{IdleResolution, IdleSpan};
+    use async_std::path::PathBuf;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::time::Duration;
+
+    fn event_log(events: Vec<Event>) -> EventLog {
+        event_log_from(State::default(), events)
+    }
+
+    fn event_log_from(initial_state: State, events: Vec<Event>) -> EventLog {
+        let mut event_log = EventLog::new(
+            PathBuf::new(),
+            NaiveDate::from_ymd_opt(2023, 11, 15).unwrap(),
+            NaiveTime::MIN,
+            initial_state,
+            FakeClock::new(local(15, 18, 0)),
+        );
+
+        for event in events {
+            event_log.add_event(event);
+        }
+
+        event_log
+    }
+
+    fn tasks(time: DateTime<FixedOffset>, ids: &[u32]) -> Event {
+        let ids = ids.iter().map(|&id| task(id)).collect::<BTreeSet<_>>();
+
+        Event::tasks(time, ids, BTreeMap::new()).unwrap()
+    }
+
+    #[test]
+    fn clean_day_has_no_diagnostics() {
+        let span = IdleSpan {
+            start: time(14, 0),
+            end: time(14, 30),
+        };
+
+        let event_log = event_log(vec![
+            Event::clock_in(time(8, 0), ClockType::Day),
+            tasks(time(9, 0), &[1]),
+            Event::clock_in(time(10, 0), ClockType::Break),
+            Event::clock_out(time(10, 15), ClockType::Break),
+            Event::clock_in(time(12, 0), ClockType::Lunch),
+            Event::clock_out(time(12, 30), ClockType::Lunch),
+            Event::idle(span.start),
+            Event::active(span.end),
+            Event::resolve_idle(time(14, 31), span, IdleResolution::Break),
+            Event::clock_out(time(17, 0), ClockType::Day),
+        ]);
+
+        assert_eq!(check(&event_log), vec![]);
+    }
+
+    #[test]
+    fn clock_in_while_clocked_in() {
+        let event_log = event_log(vec![
+            Event::clock_in(time(8, 0), ClockType::Day),
+            Event::clock_in(time(9, 0), ClockType::Day),
+        ]);
+
+        assert_eq!(
+            check(&event_log),
+            vec![Diagnostic::ClockInWhileClockedIn {
+                time: time(9, 0),
+                clock: ClockType::Day,
+            }]
+        );
+    }
+
+    #[test]
+    fn clock_out_without_clock_in() {
+        let event_log = event_log(vec![
+            Event::clock_in(time(8, 0), ClockType::Day),
+            Event::clock_out(time(9, 0), ClockType::Break),
+        ]);
+
+        assert_eq!(
+            check(&event_log),
+            vec![Diagnostic::ClockOutWithoutClockIn {
+                time: time(9, 0),
+                clock: ClockType::Break,
+            }]
+        );
+    }
+
+    #[test]
+    fn breaks_and_lunch_overlap() {
+        let event_log = event_log(vec![
+            Event::clock_in(time(8, 0), ClockType::Day),
+            Event::clock_in(time(10, 0), ClockType::Break),
+            Event::clock_in(time(10, 10), ClockType::Lunch),
+            Event::clock_in(time(10, 20), ClockType::Break),
+        ]);
+
+        assert_eq!(
+            check(&event_log),
+            vec![
+                Diagnostic::LunchDuringBreak { time: time(10, 10) },
+                Diagnostic::BreakDuringLunch { time: time(10, 20) },
+            ]
+        );
+    }
+
+    #[test]
+    fn event_outside_date() {
+        let tomorrow = time(9, 0) + chrono::Duration::days(1);
+        let event_log = event_log(vec![
+            Event::clock_in(time(8, 0), ClockType::Day),
+            Event::clock_out(tomorrow, ClockType::Day),
+        ]);
+
+        assert_eq!(
+            check(&event_log),
+            vec![Diagnostic::EventOutsideDate { time: tomorrow }]
+        );
+    }
+
+    #[test]
+    fn idle_resolved_without_span() {
+        let span = IdleSpan {
+            start: time(9, 0),
+            end: time(9, 30),
+        };
+
+        let event_log = event_log(vec![
+            Event::clock_in(time(8, 0), ClockType::Day),
+            Event::resolve_idle(time(10, 0), span, IdleResolution::Work),
+        ]);
+
+        assert_eq!(
+            check(&event_log),
+            vec![Diagnostic::IdleResolvedWithoutSpan { time: time(10, 0) }]
+        );
+    }
+
+    #[test]
+    fn task_time_exceeds_work_time() {
+        // Carried over with a task running but without being clocked in
+        let mut initial_state = State::default();
+        initial_state.tasks.resume(time(7, 0));
+
+        let event_log = event_log_from(
+            initial_state,
+            vec![tasks(time(7, 0), &[1]), tasks(time(9, 0), &[])],
+        );
+
+        assert_eq!(
+            check(&event_log),
+            vec![Diagnostic::TaskTimeExceedsWorkTime {
+                task_time: hours(2),
+                work_time: Duration::ZERO,
+            }]
+        );
+    }
+
+    #[test]
+    fn running_task_time_exceeds_work_time() {
+        let mut initial_state = State::default();
+        initial_state.tasks.resume(time(7, 0));
+
+        let event_log = event_log_from(initial_state, vec![tasks(time(7, 0), &[1])]);
+
+        assert_eq!(
+            check(&event_log),
+            vec![Diagnostic::TaskTimeExceedsWorkTime {
+                task_time: hours(11),
+                work_time: Duration::ZERO,
+            }]
+        );
+    }
+
+    #[test]
+    fn tampered_state_mismatches() {
+        let event_log = event_log(vec![Event::clock_in(time(8, 0), ClockType::Day)]);
+
+        let mut json = serde_json::to_value(&event_log).unwrap();
+        json["currentState"]["working"] = serde_json::to_value(State::default().working).unwrap();
+        let tampered: EventLog = serde_json::from_value(json).unwrap();
+
+        assert_eq!(
+            check(&tampered),
+            vec![Diagnostic::StateMismatch {
+                field: "working".to_owned(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,871 @@
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archive::{self, Archives},
+    settings::Schedule,
+    tasks::TaskID,
+    timecard::{self, DayStatus, ElapsedSummary, EventLog, State},
+};
+
+// Longest range that can be loaded at once, to keep a bad request from reading every log
+const MAX_RANGE_DAYS: i64 = 3 * 366;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaySummary {
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    pub elapsed: ElapsedSummary,
+    pub tasks: BTreeMap<TaskID, std::time::Duration>,
+    pub note: Option<String>,
+    pub event_notes: Vec<EventNote>,
+    pub status: DayStatus,
+    pub pomodoros: BTreeMap<TaskID, u32>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EventNote {
+    pub time: DateTime<FixedOffset>,
+    pub note: String,
+}
+
+impl DaySummary {
+    pub fn from_event_log(event_log: &EventLog) -> DaySummary {
+        DaySummary {
+            date: event_log.date(),
+            elapsed: event_log.elapsed(),
+            tasks: event_log.task_elapsed(),
+            note: event_log.note().map(str::to_owned),
+            event_notes: event_log
+                .events()
+                .iter()
+                .filter_map(|event| {
+                    Some(EventNote {
+                        time: event.time(),
+                        note: event.note()?.to_owned(),
+                    })
+                })
+                .collect(),
+            status: event_log.status(),
+            pomodoros: event_log.pomodoros().clone(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct History {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub days: Vec<DaySummary>,
+    pub task_totals: BTreeMap<TaskID, std::time::Duration>,
+}
+
+// Loads a summary of every logged day between `start` and `end` inclusive, using the
+// in-memory event log for the current day since it may have unsaved changes. Each day's status
+// comes from the schedule, which is what counts the time off.
+pub async fn load_range(
+    logs_dir: &Path,
+    start: NaiveDate,
+    end: NaiveDate,
+    current: &EventLog,
+    schedule: &Schedule,
+) -> Result<History, Box<dyn Error>> {
+    if end < start {
+        return Err(format!("history range ends ({}) before it starts ({})", end, start).into());
+    }
+
+    if (end - start).num_days() >= MAX_RANGE_DAYS {
+        return Err(format!("history range is longer than {} days", MAX_RANGE_DAYS).into());
+    }
+
+    let mut days = Vec::new();
+    let mut archives = Archives::default();
+
+    for date in start.iter_days().take_while(|&date| date <= end) {
+        if let Some(summary) = load_day(logs_dir, date, current, &mut archives).await? {
+            days.push(DaySummary {
+                status: schedule.status_for(date),
+                ..summary
+            });
+        }
+    }
+
+    let mut task_totals = BTreeMap::new();
+
+    for day in &days {
+        for (&id, &duration) in &day.tasks {
+            *task_totals.entry(id).or_default() += duration;
+        }
+    }
+
+    Ok(History {
+        start,
+        end,
+        days,
+        task_totals,
+    })
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Period {
+    Week,
+    Month,
+}
+
+impl Period {
+    pub fn bounds(self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            Period::Week => {
+                let start = date - chrono::Days::new(date.weekday().num_days_from_monday().into());
+
+                (start, start + chrono::Days::new(6))
+            }
+            Period::Month => {
+                let start = date.with_day(1).unwrap();
+
+                (start, start + chrono::Months::new(1) - chrono::Days::new(1))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodSummary {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+
+    pub days_logged: usize,
+    pub workdays: usize,
+
+    pub worked_time: std::time::Duration,
+    pub break_time: std::time::Duration,
+    pub lunch_time: std::time::Duration,
+    pub idle_work_time: std::time::Duration,
+    // Time off, which the schedule has already taken out of the target
+    pub credited_time: std::time::Duration,
+
+    pub target: std::time::Duration,
+    pub over: std::time::Duration,
+    pub under: std::time::Duration,
+}
+
+// Summarizes the days between `start` and `end` inclusive. Only workdays up to `today` count
+// towards the target, so a period in progress isn't shown as behind for days yet to come.
+pub fn summarize_period(
+    days: &[DaySummary],
+    start: NaiveDate,
+    end: NaiveDate,
+    today: NaiveDate,
+    schedule: &Schedule,
+) -> PeriodSummary {
+    let days: Vec<_> = days
+        .iter()
+        .filter(|day| start <= day.date && day.date <= end)
+        .collect();
+
+    let dates_so_far = start
+        .iter_days()
+        .take_while(|&date| date <= end && date <= today);
+
+    let workdays = dates_so_far
+        .clone()
+        .filter(|&date| schedule.is_workday(date))
+        .count();
+
+    let worked_time = days.iter().map(|day| day.elapsed.worked_time()).sum();
+    let target = dates_so_far
+        .clone()
+        .map(|date| schedule.target_for(date))
+        .sum();
+
+    // Days off can be marked ahead of time, but aren't credited until they come
+    let credited_time = dates_so_far.map(|date| schedule.credited_for(date)).sum();
+
+    PeriodSummary {
+        start,
+        end,
+
+        days_logged: days.len(),
+        workdays,
+
+        worked_time,
+        break_time: days.iter().map(|day| day.elapsed.break_time).sum(),
+        lunch_time: days.iter().map(|day| day.elapsed.lunch_time).sum(),
+        idle_work_time: days.iter().map(|day| day.elapsed.idle_work_time).sum(),
+        credited_time,
+
+        target,
+        over: worked_time.saturating_sub(target),
+        under: target.saturating_sub(worked_time),
+    }
+}
+
+// Loads a summary for each week or month overlapping `start` through `end`
+pub async fn load_periods(
+    logs_dir: &Path,
+    period: Period,
+    start: NaiveDate,
+    end: NaiveDate,
+    current: &EventLog,
+    schedule: &Schedule,
+) -> Result<Vec<PeriodSummary>, Box<dyn Error>> {
+    let (range_start, _) = period.bounds(start);
+    let (_, range_end) = period.bounds(end);
+
+    let history = load_range(logs_dir, range_start, range_end, current, schedule).await?;
+
+    let mut summaries = Vec::new();
+    let mut period_start = range_start;
+
+    while period_start <= range_end {
+        let (_, period_end) = period.bounds(period_start);
+
+        summaries.push(summarize_period(
+            &history.days,
+            period_start,
+            period_end,
+            current.date(),
+            schedule,
+        ));
+
+        period_start = period_end + chrono::Days::new(1);
+    }
+
+    Ok(summaries)
+}
+
+// Running total of time worked over or under the work target, only one of which is ever nonzero
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlexBalance {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub over: std::time::Duration,
+    pub under: std::time::Duration,
+}
+
+impl FlexBalance {
+    fn from_period(summary: &PeriodSummary) -> FlexBalance {
+        FlexBalance {
+            start: summary.start,
+            end: summary.end,
+            over: summary.over,
+            under: summary.under,
+        }
+    }
+
+    // Carries the balance on through a period that follows it
+    fn extend(&self, summary: &PeriodSummary) -> FlexBalance {
+        let over = self.over + summary.over;
+        let under = self.under + summary.under;
+
+        FlexBalance {
+            start: self.start,
+            end: summary.end,
+            over: over.saturating_sub(under),
+            under: under.saturating_sub(over),
+        }
+    }
+
+    pub fn balance(&self) -> chrono::Duration {
+        let over =
+            chrono::Duration::from_std(self.over).unwrap_or_else(|_| chrono::Duration::zero());
+        let under =
+            chrono::Duration::from_std(self.under).unwrap_or_else(|_| chrono::Duration::zero());
+
+        over - under
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlexSummary {
+    pub total: FlexBalance,
+    pub week: FlexBalance,
+}
+
+// Flex time banked before the current day. Past days rarely change, so this is kept around and
+// only the current day gets added on each time.
+#[derive(Clone)]
+pub struct FlexBank {
+    date: NaiveDate,
+    configured_start: Option<NaiveDate>,
+    schedule: Schedule,
+
+    total: FlexBalance,
+    week: FlexBalance,
+}
+
+impl FlexBank {
+    // Banks every day from `configured_start`, or the first logged day if there isn't one, up to
+    // the current day
+    pub async fn load(
+        logs_dir: &Path,
+        configured_start: Option<NaiveDate>,
+        current: &EventLog,
+        schedule: &Schedule,
+    ) -> Result<FlexBank, Box<dyn Error>> {
+        let date = current.date();
+        let start = match configured_start {
+            Some(start) => start,
+            None => first_logged_day(logs_dir).await?.unwrap_or(date),
+        }
+        .min(date);
+        let yesterday = date - chrono::Days::new(1);
+        let week_start = Period::Week.bounds(date).0.max(start);
+
+        let mut days = Vec::new();
+        let mut archives = Archives::default();
+
+        for day in start.iter_days().take_while(|&day| day < date) {
+            if let Some(summary) = load_day(logs_dir, day, current, &mut archives).await? {
+                days.push(summary);
+            }
+        }
+
+        Ok(FlexBank {
+            date,
+            configured_start,
+            schedule: schedule.clone(),
+
+            total: FlexBalance::from_period(&summarize_period(
+                &days, start, yesterday, yesterday, schedule,
+            )),
+            week: FlexBalance::from_period(&summarize_period(
+                &days, week_start, yesterday, yesterday, schedule,
+            )),
+        })
+    }
+
+    pub fn is_current(
+        &self,
+        configured_start: Option<NaiveDate>,
+        date: NaiveDate,
+        schedule: &Schedule,
+    ) -> bool {
+        self.configured_start == configured_start && self.date == date && &self.schedule == schedule
+    }
+
+    pub fn with_today(&self, current: &EventLog) -> FlexSummary {
+        let today = summarize_period(
+            &[DaySummary::from_event_log(current)],
+            self.date,
+            self.date,
+            self.date,
+            &self.schedule,
+        );
+
+        FlexSummary {
+            total: self.total.extend(&today),
+            week: self.week.extend(&today),
+        }
+    }
+}
+
+pub async fn first_logged_day(logs_dir: &Path) -> Result<Option<NaiveDate>, Box<dyn Error>> {
+    let mut first = archive::first_archived_day(logs_dir).await?;
+
+    for entry in std::fs::read_dir(logs_dir)? {
+        if let Some(date) = timecard::date_for_log_file(&entry?.path()) {
+            first = Some(first.map_or(date, |first: NaiveDate| first.min(date)));
+        }
+    }
+
+    Ok(first)
+}
+
+// Loads a day from its own log if it has one, otherwise from the archives
+pub async fn load_day(
+    logs_dir: &Path,
+    date: NaiveDate,
+    current: &EventLog,
+    archives: &mut Archives,
+) -> Result<Option<DaySummary>, Box<dyn Error>> {
+    if date == current.date() {
+        return Ok(Some(DaySummary::from_event_log(current)));
+    }
+
+    let log_file = timecard::log_file_for_date(logs_dir, date);
+
+    if !timecard::EventLog::exists(log_file.clone().into()).await {
+        return archives.summary(logs_dir, date).await;
+    }
+
+    let event_log = EventLog::load(log_file.into(), current.clock()).await?;
+
+    Ok(Some(DaySummary::from_event_log(&event_log)))
+}
+
+// Makes a change to any logged day, writing past days straight back out since they're compacted
+pub async fn edit_day<F>(
+    logs_dir: &Path,
+    date: NaiveDate,
+    current: &mut EventLog,
+    edit: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&mut EventLog) -> Result<(), Box<dyn Error>>,
+{
+    if date == current.date() {
+        edit(current)?;
+        return current.save().await;
+    }
+
+    let log_file = timecard::log_file_for_date(logs_dir, date);
+
+    if !timecard::EventLog::exists(log_file.clone().into()).await
+        && !archive::restore_day(logs_dir, date, current.clock()).await?
+    {
+        return Err(format!("no timecard for {}", date).into());
+    }
+
+    let mut event_log = EventLog::load(log_file.into(), current.clock()).await?;
+    edit(&mut event_log)?;
+    event_log.compact().await
+}
+
+pub async fn set_day_note(
+    logs_dir: &Path,
+    date: NaiveDate,
+    note: Option<String>,
+    current: &mut EventLog,
+) -> Result<(), Box<dyn Error>> {
+    edit_day(logs_dir, date, current, |event_log| {
+        event_log.set_note(note);
+        Ok(())
+    })
+    .await
+}
+
+// Records a day's status in its log, creating the log if the day is taken off but wasn't logged or
+// hasn't happened yet. The schedule is what counts the time off, so archived days are left alone
+// rather than restored.
+pub async fn set_day_status(
+    logs_dir: &Path,
+    date: NaiveDate,
+    status: DayStatus,
+    current: &mut EventLog,
+) -> Result<(), Box<dyn Error>> {
+    let log_file = timecard::log_file_for_date(logs_dir, date);
+
+    if date != current.date() && !EventLog::exists(log_file.clone().into()).await {
+        if status.is_worked() || archive::is_archived(logs_dir, date).await? {
+            return Ok(());
+        }
+
+        let mut event_log = EventLog::new(
+            log_file.into(),
+            date,
+            current.day_start(),
+            State::default(),
+            current.clock(),
+        );
+        event_log.set_status(status);
+
+        return event_log.compact().await;
+    }
+
+    edit_day(logs_dir, date, current, |event_log| {
+        event_log.set_status(status);
+        Ok(())
+    })
+    .await
+}
+
+// Starts the log for a new day, keeping anything marked on it ahead of time
+pub async fn start_day(
+    logs_dir: &Path,
+    date: NaiveDate,
+    day_start: NaiveTime,
+    initial_state: State,
+    current: &EventLog,
+) -> Result<EventLog, Box<dyn Error>> {
+    let log_file = timecard::log_file_for_date(logs_dir, date);
+
+    let mut event_log = EventLog::new(
+        log_file.clone().into(),
+        date,
+        day_start,
+        initial_state,
+        current.clock(),
+    );
+
+    if EventLog::exists(log_file.clone().into()).await {
+        let planned = EventLog::load(log_file.into(), current.clock()).await?;
+
+        event_log.set_status(planned.status());
+        event_log.set_note(planned.note().map(str::to_owned));
+    }
+
+    event_log.save().await?;
+
+    Ok(event_log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::settings::{ExceptionKind, ScheduleException};
+    use crate::test_util::{event_log_in, hours, local, minutes, task, temp_dir};
+    use crate::timecard::{ClockType, Event};
+    use std::{collections::BTreeSet, sync::Arc, time::Duration};
+
+    async fn write_day(logs_dir: &Path, clock: &Arc<FakeClock>, day: u32, hours: i64) {
+        clock.set(local(day, 9, 0));
+
+        let mut event_log = event_log_in(logs_dir, clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.add_event(
+            Event::tasks(event_log.now(), BTreeSet::from([task(1)]), BTreeMap::new()).unwrap(),
+        );
+        clock.advance(chrono::Duration::hours(hours));
+        event_log.add_event(Event::clock_out(event_log.now(), ClockType::Day));
+
+        event_log.compact().await.unwrap();
+    }
+
+    fn day(day: u32, work_hours: u64, lunch_hours: u64) -> DaySummary {
+        DaySummary {
+            date: NaiveDate::from_ymd_opt(2023, 11, day).unwrap(),
+            elapsed: ElapsedSummary {
+                work_time: hours(work_hours),
+                break_time: minutes(15),
+                lunch_time: hours(lunch_hours),
+                idle_work_time: Duration::ZERO,
+
+                working: false,
+                on_break: false,
+                on_lunch: false,
+                idle_work: false,
+
+                categories: BTreeMap::new(),
+            },
+            tasks: BTreeMap::new(),
+            note: None,
+            event_notes: Vec::new(),
+            status: DayStatus::Worked,
+            pomodoros: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn period_bounds() {
+        let date = NaiveDate::from_ymd_opt(2023, 11, 15).unwrap();
+
+        assert_eq!(
+            Period::Week.bounds(date),
+            (
+                NaiveDate::from_ymd_opt(2023, 11, 13).unwrap(),
+                NaiveDate::from_ymd_opt(2023, 11, 19).unwrap()
+            )
+        );
+        assert_eq!(
+            Period::Month.bounds(date),
+            (
+                NaiveDate::from_ymd_opt(2023, 11, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2023, 11, 30).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn summarize_period_compares_against_workdays_so_far() {
+        let (start, end) = Period::Week.bounds(NaiveDate::from_ymd_opt(2023, 11, 15).unwrap());
+        let days = [day(13, 9, 1), day(14, 10, 1), day(15, 5, 0)];
+
+        let summary = summarize_period(
+            &days,
+            start,
+            end,
+            NaiveDate::from_ymd_opt(2023, 11, 15).unwrap(),
+            &Schedule::weekdays(hours(8)),
+        );
+
+        assert_eq!(summary.days_logged, 3);
+        assert_eq!(summary.workdays, 3);
+        assert_eq!(summary.worked_time, hours(22));
+        assert_eq!(summary.lunch_time, hours(2));
+        assert_eq!(summary.break_time, minutes(45));
+        assert_eq!(summary.target, hours(24));
+        assert_eq!(summary.over, Duration::ZERO);
+        assert_eq!(summary.under, hours(2));
+    }
+
+    #[test]
+    fn summarize_period_skips_weekend_targets() {
+        let (start, end) = Period::Week.bounds(NaiveDate::from_ymd_opt(2023, 11, 15).unwrap());
+        let days = [day(18, 3, 0)];
+
+        let summary = summarize_period(
+            &days,
+            start,
+            end,
+            NaiveDate::from_ymd_opt(2023, 11, 30).unwrap(),
+            &Schedule::weekdays(hours(8)),
+        );
+
+        assert_eq!(summary.workdays, 5);
+        assert_eq!(summary.target, hours(40));
+        assert_eq!(summary.worked_time, hours(3));
+    }
+
+    #[test]
+    fn summarize_period_uses_scheduled_targets() {
+        let date = NaiveDate::from_ymd_opt(2023, 11, 15).unwrap();
+        let (start, end) = Period::Week.bounds(date);
+
+        let mut schedule = Schedule::weekdays(hours(8));
+        schedule.weekly[4] = hours(6);
+        schedule.exceptions.insert(
+            date,
+            ScheduleException {
+                kind: ExceptionKind::Holiday,
+                target: Duration::ZERO,
+                note: None,
+            },
+        );
+
+        let summary = summarize_period(&[], start, end, end, &schedule);
+
+        assert_eq!(summary.workdays, 4);
+        assert_eq!(summary.target, hours(30));
+    }
+
+    #[test]
+    fn load_range_skips_missing_days() {
+        async_std::task::block_on(async {
+            let logs_dir = temp_dir("history");
+
+            let clock = FakeClock::new(local(13, 9, 0));
+
+            write_day(&logs_dir, &clock, 13, 8).await;
+            write_day(&logs_dir, &clock, 15, 6).await;
+
+            clock.set(local(16, 9, 0));
+            let current = event_log_in(&logs_dir, &clock);
+
+            let history = load_range(
+                &logs_dir,
+                NaiveDate::from_ymd_opt(2023, 11, 13).unwrap(),
+                NaiveDate::from_ymd_opt(2023, 11, 16).unwrap(),
+                &current,
+                &Schedule::weekdays(hours(8)),
+            )
+            .await
+            .unwrap();
+
+            let dates: Vec<_> = history.days.iter().map(|day| day.date.day()).collect();
+            assert_eq!(dates, vec![13, 15, 16]);
+
+            assert_eq!(history.days[0].elapsed.work_time, hours(8));
+            assert_eq!(history.days[1].elapsed.work_time, hours(6));
+            assert_eq!(history.days[2].elapsed.work_time, Duration::ZERO);
+            assert_eq!(history.task_totals.get(&task(1)), Some(&hours(14)));
+
+            std::fs::remove_dir_all(&logs_dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn flex_bank_carries_across_days() {
+        async_std::task::block_on(async {
+            let logs_dir = temp_dir("history-flex");
+
+            let clock = FakeClock::new(local(10, 9, 0));
+
+            // Friday of the week before, then nothing logged on the 14th
+            write_day(&logs_dir, &clock, 10, 10).await;
+            write_day(&logs_dir, &clock, 13, 9).await;
+
+            clock.set(local(15, 9, 0));
+            let mut current = event_log_in(&logs_dir, &clock);
+            current.add_event(Event::clock_in(current.now(), ClockType::Day));
+            clock.advance(chrono::Duration::hours(10));
+
+            let date = |day| NaiveDate::from_ymd_opt(2023, 11, day).unwrap();
+            let schedule = Schedule::weekdays(hours(8));
+
+            assert_eq!(first_logged_day(&logs_dir).await.unwrap(), Some(date(10)));
+
+            let bank = FlexBank::load(&logs_dir, None, &current, &schedule)
+                .await
+                .unwrap();
+            assert!(bank.is_current(None, date(15), &schedule));
+            assert!(!bank.is_current(Some(date(13)), date(15), &schedule));
+            assert!(!bank.is_current(None, date(15), &Schedule::weekdays(hours(6))));
+
+            let flex = bank.with_today(&current);
+            assert_eq!((flex.total.start, flex.total.end), (date(10), date(15)));
+            assert_eq!(
+                (flex.total.over, flex.total.under),
+                (Duration::ZERO, hours(3))
+            );
+            assert_eq!((flex.week.start, flex.week.end), (date(13), date(15)));
+            assert_eq!(
+                (flex.week.over, flex.week.under),
+                (Duration::ZERO, hours(5))
+            );
+            assert_eq!(flex.week.balance(), chrono::Duration::hours(-5));
+
+            let bank = FlexBank::load(&logs_dir, Some(date(15)), &current, &schedule)
+                .await
+                .unwrap();
+
+            let flex = bank.with_today(&current);
+            assert_eq!(
+                (flex.total.over, flex.total.under),
+                (hours(2), Duration::ZERO)
+            );
+            assert_eq!(
+                (flex.week.over, flex.week.under),
+                (hours(2), Duration::ZERO)
+            );
+
+            std::fs::remove_dir_all(&logs_dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn days_off_are_credited() {
+        async_std::task::block_on(async {
+            let logs_dir = temp_dir("history-status");
+
+            let clock = FakeClock::new(local(13, 9, 0));
+            write_day(&logs_dir, &clock, 13, 4).await;
+
+            clock.set(local(15, 9, 0));
+            let mut current = event_log_in(&logs_dir, &clock);
+
+            let date = |day| NaiveDate::from_ymd_opt(2023, 11, day).unwrap();
+
+            // A half day that was logged, a sick day that wasn't, and a holiday still to come
+            let partial = DayStatus::PartialPto { credited: hours(4) };
+            let mut schedule = Schedule::weekdays(hours(8));
+
+            for (day, status) in [
+                (13, partial),
+                (14, DayStatus::Sick),
+                (16, DayStatus::Holiday),
+            ] {
+                schedule.set_status(date(day), status);
+                set_day_status(&logs_dir, date(day), status, &mut current)
+                    .await
+                    .unwrap();
+            }
+
+            let history = load_range(&logs_dir, date(13), date(16), &current, &schedule)
+                .await
+                .unwrap();
+            let statuses: Vec<_> = history.days.iter().map(|day| day.status).collect();
+            assert_eq!(
+                statuses,
+                vec![
+                    partial,
+                    DayStatus::Sick,
+                    DayStatus::Worked,
+                    DayStatus::Holiday
+                ]
+            );
+            assert_eq!(history.days[0].elapsed.work_time, hours(4));
+
+            let summary = summarize_period(&history.days, date(13), date(19), date(15), &schedule);
+            assert_eq!(summary.credited_time, hours(12));
+            assert_eq!(summary.target, hours(12));
+            assert_eq!(summary.under, hours(8));
+
+            // Only the schedule counts days off, even when a log says otherwise
+            schedule.set_status(date(15), DayStatus::Pto);
+            set_day_status(&logs_dir, date(14), DayStatus::Worked, &mut current)
+                .await
+                .unwrap();
+
+            let history = load_range(&logs_dir, date(13), date(16), &current, &schedule)
+                .await
+                .unwrap();
+            assert_eq!(history.days[1].status, DayStatus::Sick);
+            assert_eq!(history.days[2].status, DayStatus::Pto);
+
+            let summary = summarize_period(&history.days, date(13), date(19), date(15), &schedule);
+            assert_eq!(summary.credited_time, hours(20));
+            assert_eq!(summary.target, hours(4));
+            assert_eq!(summary.over, hours(0));
+            assert_eq!(summary.under, hours(0));
+
+            // The holiday is still there once the day comes around
+            let next = start_day(
+                &logs_dir,
+                date(16),
+                NaiveTime::MIN,
+                State::default(),
+                &current,
+            )
+            .await
+            .unwrap();
+            assert_eq!(next.status(), DayStatus::Holiday);
+            assert!(next.events().is_empty());
+
+            std::fs::remove_dir_all(&logs_dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn day_notes_flow_into_history() {
+        async_std::task::block_on(async {
+            let logs_dir = temp_dir("history-notes");
+
+            let clock = FakeClock::new(local(13, 9, 0));
+            write_day(&logs_dir, &clock, 13, 8).await;
+
+            clock.set(local(16, 9, 0));
+            let mut current = event_log_in(&logs_dir, &clock);
+
+            let clock_in = Event::clock_in(current.now(), ClockType::Day);
+            current.add_event(clock_in.clone());
+            current
+                .set_event_note(&clock_in, Some("late start".to_owned()))
+                .unwrap();
+
+            let date = |day| NaiveDate::from_ymd_opt(2023, 11, day).unwrap();
+
+            set_day_note(
+                &logs_dir,
+                date(13),
+                Some("offsite".to_owned()),
+                &mut current,
+            )
+            .await
+            .unwrap();
+            set_day_note(&logs_dir, date(16), Some("  ".to_owned()), &mut current)
+                .await
+                .unwrap();
+            assert!(set_day_note(
+                &logs_dir,
+                date(14),
+                Some("missing".to_owned()),
+                &mut current
+            )
+            .await
+            .is_err());
+
+            let schedule = Schedule::weekdays(hours(8));
+            let history = load_range(&logs_dir, date(13), date(16), &current, &schedule)
+                .await
+                .unwrap();
+
+            assert_eq!(history.days[0].note.as_deref(), Some("offsite"));
+            assert_eq!(history.days[1].note, None);
+            assert_eq!(history.days[1].event_notes.len(), 1);
+            assert_eq!(history.days[1].event_notes[0].note, "late start");
+
+            std::fs::remove_dir_all(&logs_dir).unwrap();
+        });
+    }
+}
@@ -0,0 +1,289 @@
+use std::error::Error;
+
+use async_std::{
+    fs::{self, OpenOptions},
+    io::WriteExt,
+    path::{Path, PathBuf},
+};
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    schema, storage,
+    timecard::{Event, EventLog},
+};
+
+// Compact the journal down to a single snapshot after this many appended entries
+const SNAPSHOT_INTERVAL: usize = 200;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "entry")]
+pub enum JournalEntry {
+    Snapshot(#[serde(with = "schema::nested")] Box<EventLog>),
+    Event(Event),
+    ActiveUntil { time: Option<DateTime<FixedOffset>> },
+}
+
+// Tracks changes to an event log that haven't been written to its journal yet
+#[derive(Clone)]
+pub struct Journal {
+    pending: Vec<JournalEntry>,
+    needs_snapshot: bool,
+    entries_since_snapshot: usize,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Journal {
+            pending: Vec::new(),
+            needs_snapshot: true,
+            entries_since_snapshot: 0,
+        }
+    }
+}
+
+impl Journal {
+    pub fn loaded(entries_since_snapshot: usize) -> Journal {
+        Journal {
+            pending: Vec::new(),
+            needs_snapshot: false,
+            entries_since_snapshot,
+        }
+    }
+
+    pub fn push(&mut self, entry: JournalEntry) {
+        if !self.needs_snapshot {
+            self.pending.push(entry);
+        }
+    }
+
+    pub fn request_snapshot(&mut self) {
+        self.needs_snapshot = true;
+        self.pending.clear();
+    }
+
+    pub async fn flush(
+        &mut self,
+        filename: &Path,
+        event_log: &EventLog,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.needs_snapshot
+            || self.entries_since_snapshot + self.pending.len() > SNAPSHOT_INTERVAL
+        {
+            let snapshot = JournalEntry::Snapshot(Box::new(event_log.clone()));
+
+            let mut json = serde_json::to_vec(&snapshot)?;
+            json.push(b'\n');
+
+            storage::write_atomic(filename, &json).await?;
+
+            self.needs_snapshot = false;
+            self.entries_since_snapshot = 0;
+        } else if !self.pending.is_empty() {
+            let mut json = Vec::new();
+
+            for entry in &self.pending {
+                serde_json::to_writer(&mut json, entry)?;
+                json.push(b'\n');
+            }
+
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(filename)
+                .await?;
+            file.write_all(&json).await?;
+            file.sync_data().await?;
+
+            self.entries_since_snapshot += self.pending.len();
+        }
+
+        self.pending.clear();
+
+        Ok(())
+    }
+}
+
+pub fn filename_for(log_filename: &Path) -> PathBuf {
+    log_filename.with_extension("jsonl")
+}
+
+// Reads every entry in the journal, along with whether a truncated last entry had to be dropped
+pub async fn read(filename: &Path) -> Result<(Vec<JournalEntry>, bool), Box<dyn Error>> {
+    let buf = fs::read(filename).await?;
+    let lines: Vec<&[u8]> = buf
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let mut entries = Vec::with_capacity(lines.len());
+    let mut truncated = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_slice(line) {
+            Ok(entry) => entries.push(entry),
+            // A crash mid-append can only leave the last line partially written
+            Err(err) if i == lines.len() - 1 => {
+                println!(
+                    "ignoring truncated journal entry in {:?}: {}",
+                    filename, err
+                );
+                truncated = true;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok((entries, truncated))
+}
+
+pub async fn remove(filename: &Path) -> Result<(), Box<dyn Error>> {
+    if filename.exists().await {
+        fs::remove_file(filename).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::test_util::{event_log_in, local, temp_dir};
+    use crate::timecard::ClockType;
+    use std::sync::Arc;
+
+    async fn build_event_log(dir: &Path, clock: &Arc<FakeClock>) -> EventLog {
+        let mut event_log = event_log_in(dir, clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.force_active();
+        event_log.save().await.unwrap();
+
+        clock.advance(chrono::Duration::minutes(30));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Break));
+        event_log.refresh_active().await.unwrap();
+
+        event_log
+    }
+
+    #[test]
+    fn load_replays_appended_entries() {
+        async_std::task::block_on(async {
+            let dir = PathBuf::from(temp_dir("journal-replay"));
+            let clock = FakeClock::new(local(15, 9, 0));
+
+            let event_log = build_event_log(&dir, &clock).await;
+            let journal_filename = dir.join("2023-11-15.log.jsonl");
+
+            let journal = fs::read_to_string(&journal_filename).await.unwrap();
+            assert_eq!(journal.lines().count(), 3);
+            assert!(!dir.join("2023-11-15.log.json").exists().await);
+
+            let loaded = EventLog::load(dir.join("2023-11-15.log.json"), clock.clone())
+                .await
+                .unwrap();
+
+            assert_eq!(
+                serde_json::to_value(&loaded).unwrap(),
+                serde_json::to_value(&event_log).unwrap()
+            );
+
+            fs::remove_dir_all(&dir).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn load_ignores_truncated_last_entry() {
+        async_std::task::block_on(async {
+            let dir = PathBuf::from(temp_dir("journal-truncated"));
+            let clock = FakeClock::new(local(15, 9, 0));
+
+            let event_log = build_event_log(&dir, &clock).await;
+            let journal_filename = dir.join("2023-11-15.log.jsonl");
+
+            let mut file = OpenOptions::new()
+                .append(true)
+                .open(&journal_filename)
+                .await
+                .unwrap();
+            file.write_all(b"{\"entry\":\"Event\",\"type\":\"Clo")
+                .await
+                .unwrap();
+            drop(file);
+
+            let loaded = EventLog::load(dir.join("2023-11-15.log.json"), clock.clone())
+                .await
+                .unwrap();
+
+            assert_eq!(loaded.events(), event_log.events());
+
+            fs::remove_dir_all(&dir).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn append_after_truncated_entry_keeps_events() {
+        async_std::task::block_on(async {
+            let dir = PathBuf::from(temp_dir("journal-truncated-append"));
+            let clock = FakeClock::new(local(15, 9, 0));
+
+            let event_log = build_event_log(&dir, &clock).await;
+            let journal_filename = dir.join("2023-11-15.log.jsonl");
+
+            let mut file = OpenOptions::new()
+                .append(true)
+                .open(&journal_filename)
+                .await
+                .unwrap();
+            file.write_all(b"{\"entry\":\"Event\",\"type\":\"Clo")
+                .await
+                .unwrap();
+            drop(file);
+
+            // Appending straight after the partial line would glue the new entry onto it
+            for _ in 0..2 {
+                let mut loaded = EventLog::load(dir.join("2023-11-15.log.json"), clock.clone())
+                    .await
+                    .unwrap();
+
+                clock.advance(chrono::Duration::minutes(10));
+                loaded.add_event(Event::clock_out(loaded.now(), ClockType::Break));
+                loaded.save().await.unwrap();
+            }
+
+            let loaded = EventLog::load(dir.join("2023-11-15.log.json"), clock.clone())
+                .await
+                .unwrap();
+
+            assert_eq!(loaded.events().len(), event_log.events().len() + 2);
+
+            fs::remove_dir_all(&dir).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn compact_replaces_journal() {
+        async_std::task::block_on(async {
+            let dir = PathBuf::from(temp_dir("journal-compact"));
+            let clock = FakeClock::new(local(15, 9, 0));
+
+            let mut event_log = build_event_log(&dir, &clock).await;
+            event_log.compact().await.unwrap();
+
+            assert!(dir.join("2023-11-15.log.json").exists().await);
+            assert!(!dir.join("2023-11-15.log.jsonl").exists().await);
+
+            let loaded = EventLog::load(dir.join("2023-11-15.log.json"), clock.clone())
+                .await
+                .unwrap();
+
+            assert_eq!(
+                serde_json::to_value(&loaded).unwrap(),
+                serde_json::to_value(&event_log).unwrap()
+            );
+
+            fs::remove_dir_all(&dir).await.unwrap();
+        });
+    }
+}
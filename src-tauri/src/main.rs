@@ -4,29 +4,56 @@
 use async_std::sync::{Mutex, RwLock};
 use chrono::prelude::*;
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     error::Error,
     path::{Path, PathBuf},
     sync::Arc,
 };
 use tauri::{async_runtime, Manager};
 
+mod archive;
+mod clock;
+mod consistency;
+mod history;
+mod journal;
 mod notifications;
+mod pomodoro;
+mod repair;
+mod schema;
 mod settings;
+mod storage;
 mod tasks;
+#[cfg(test)]
+mod test_util;
 mod timecard;
 mod wayland;
 
+// What notifications need from the settings, copied out before locking the event log
+struct NotificationSettings {
+    schedule: settings::Schedule,
+    flex_start: Option<NaiveDate>,
+    lunch_target: std::time::Duration,
+    break_target: std::time::Duration,
+    categories: Vec<settings::CategorySettings>,
+}
+
 struct AppState {
     // data_dir: PathBuf,
     // app_dir: PathBuf,
     logs_dir: PathBuf,
     // config_file: PathBuf,
+    clock: Arc<dyn clock::Clock>,
     event_log: RwLock<timecard::EventLog>,
     settings: Mutex<settings::Settings>,
     app_handle: RwLock<Option<tauri::AppHandle>>,
     notifier: notifications::Notifier,
     task_manager: tasks::TaskManager,
+    // Flex time banked from past days, recalculated when those days or the work target change
+    flex_bank: Mutex<Option<history::FlexBank>>,
+    pomodoro: Mutex<Option<pomodoro::Pomodoro>>,
+    // Last archiving run that finished. Held while archiving and while changing past days, since
+    // archiving happens outside the event log lock.
+    archiving: Mutex<Option<archive::ArchiveRun>>,
 }
 
 impl AppState {
@@ -38,6 +65,14 @@ impl AppState {
         }
     }
 
+    async fn send_pomodoro(&self, pomodoro: &Option<pomodoro::Pomodoro>) {
+        let app_handle = self.app_handle.read().await;
+
+        if let Some(app_handle) = &*app_handle {
+            app_handle.emit_all("pomodoro", pomodoro).unwrap();
+        }
+    }
+
     async fn send_tasks(&self, tasks: &[tasks::Task]) {
         let app_handle = self.app_handle.read().await;
 
@@ -46,46 +81,175 @@ impl AppState {
         }
     }
 
+    async fn notification_settings(&self) -> NotificationSettings {
+        let settings = self.settings.lock().await;
+
+        NotificationSettings {
+            schedule: settings.schedule.clone(),
+            flex_start: settings.flex_start,
+            lunch_target: settings.lunch_target,
+            break_target: settings.break_target,
+            categories: settings.categories.clone(),
+        }
+    }
+
     async fn update_notifications(
         &self,
+        settings: &NotificationSettings,
         event_log: &timecard::EventLog,
     ) -> Result<(), Box<dyn Error>> {
         let elapsed = event_log.elapsed();
 
-        let (work_target, lunch_target, break_target) = {
-            let settings = self.settings.lock().await;
+        // The day's scheduled target already leaves out any time off
+        let work_target = settings.schedule.target_for(event_log.date());
+        let lunch_target = settings.lunch_target;
+        let break_target = settings.break_target;
 
-            (
-                settings.work_target,
-                settings.lunch_target,
-                settings.break_target,
-            )
-        };
+        if let Some(over) = elapsed.overtime(work_target) {
+            let week_flex = self
+                .flex(settings.flex_start, &settings.schedule, event_log)
+                .await?
+                .week
+                .balance()
+                - chrono::Duration::from_std(over)?;
 
-        if elapsed.working && elapsed.work_time - elapsed.lunch_time > work_target {
-            self.notifier
-                .show_overtime(elapsed.work_time - elapsed.lunch_time - work_target)
-                .await?;
+            self.notifier.show_overtime(over, week_flex).await?;
         } else {
             self.notifier.clear_overtime().await;
         }
 
-        if elapsed.on_lunch && elapsed.lunch_time > lunch_target {
-            self.notifier
-                .show_long_lunch(elapsed.lunch_time - lunch_target)
-                .await?;
+        match event_log.projection(work_target, lunch_target) {
+            timecard::Projection {
+                work_left,
+                lunch_left,
+                end: Some(end),
+            } if elapsed.working => {
+                self.notifier
+                    .show_time_left(work_left, lunch_left, end)
+                    .await?;
+            }
+            _ => self.notifier.clear_time_left().await,
+        }
+
+        if let Some(over) = elapsed.long_lunch(lunch_target) {
+            self.notifier.show_long_lunch(over).await?;
         } else {
             self.notifier.clear_long_lunch().await;
         }
 
-        if elapsed.on_break && elapsed.break_time > break_target {
-            self.notifier
-                .show_long_break(elapsed.break_time - break_target)
-                .await?;
+        if let Some(over) = elapsed.long_break(break_target) {
+            self.notifier.show_long_break(over).await?;
         } else {
             self.notifier.clear_long_break().await;
         }
 
+        for category in &settings.categories {
+            let name = &category.category.name;
+            let over = category
+                .target
+                .and_then(|target| elapsed.long_category(name, target));
+
+            if let Some(over) = over {
+                self.notifier.show_long_category(name, over).await?;
+            } else {
+                self.notifier.clear_long_category(name).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Moves the pomodoro timer on to its next phase once the current one is over
+    async fn tick_pomodoro(&self) -> Result<(), Box<dyn Error>> {
+        let mut pomodoro = self.pomodoro.lock().await;
+
+        let Some(timer) = &mut *pomodoro else {
+            return Ok(());
+        };
+
+        if self.clock.now() < timer.phase_end {
+            return Ok(());
+        }
+
+        self.refresh_date(false, true, false).await?;
+
+        let notification_settings = self.notification_settings().await;
+        let mut event_log = self.event_log.write().await;
+
+        if let Some(ended) = timer.advance(&mut event_log) {
+            event_log.save().await?;
+            self.notifier
+                .show_pomodoro(ended, timer.next_length())
+                .await?;
+
+            self.send_event_log(&event_log).await;
+            self.send_pomodoro(&pomodoro).await;
+            self.update_notifications(&notification_settings, &event_log)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn flex(
+        &self,
+        flex_start: Option<NaiveDate>,
+        schedule: &settings::Schedule,
+        event_log: &timecard::EventLog,
+    ) -> Result<history::FlexSummary, Box<dyn Error>> {
+        let mut flex_bank = self.flex_bank.lock().await;
+
+        let current = flex_bank
+            .as_ref()
+            .is_some_and(|bank| bank.is_current(flex_start, event_log.date(), schedule));
+
+        if !current {
+            *flex_bank = Some(
+                history::FlexBank::load(&self.logs_dir, flex_start, event_log, schedule).await?,
+            );
+        }
+
+        Ok(flex_bank.as_ref().unwrap().with_today(event_log))
+    }
+
+    // Packs up last month's logs once a new month starts, or tries again if the last run failed
+    async fn archive_old_logs(
+        &self,
+        today: NaiveDate,
+        retention_days: Option<u32>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut archiving = self.archiving.lock().await;
+
+        if archiving.as_ref().is_some_and(|run| run.is_current(today)) {
+            return Ok(());
+        }
+
+        let run = match archive::archive_old_logs(
+            &self.logs_dir,
+            today,
+            retention_days,
+            archiving.as_ref(),
+            self.clock.clone(),
+        )
+        .await
+        {
+            Ok(run) => run,
+            Err(err) => {
+                println!("error archiving old logs: {}", err);
+                return Ok(());
+            }
+        };
+
+        let warnings = &archiving.insert(run).warnings;
+
+        if !warnings.is_empty() {
+            for warning in warnings {
+                println!("warning: {}", warning);
+            }
+
+            self.notifier.show_repair_warnings(warnings).await?;
+        }
+
         Ok(())
     }
 
@@ -97,28 +261,40 @@ impl AppState {
     ) -> Result<bool, Box<dyn Error>> {
         let mut settings = self.settings.lock().await;
         let mut event_log = self.event_log.write().await;
-        let current_date = Local::now().date_naive();
+        let current_date = timecard::workday_for(&self.clock.now(), settings.day_start);
+        let new_day = settings.current_date != current_date;
 
         // Add idle event if needed
         let injected_idle = event_log.infer_idle();
 
-        if settings.current_date != current_date {
-            // Save changes to old event log before we create a new one
-            if injected_idle {
+        // Close out the day if we walked away without clocking out
+        if let Some(idle_limit) = settings.auto_clock_out {
+            if let Some(time) = event_log.auto_clock_out(idle_limit) {
                 event_log.save().await?;
+                self.notifier.show_auto_clock_out(time).await?;
+
+                if send {
+                    self.send_event_log(&event_log).await;
+                }
             }
+        }
+
+        if new_day {
+            // Write out the finished event log before we create a new one
+            event_log.compact().await?;
 
             // Create new event log
             let mut new_state = event_log.get_state();
             new_state.reset_accumulations();
 
-            let new_event_log = timecard::EventLog::new(
-                log_file_for_date(&self.logs_dir, current_date).into(),
+            *event_log = history::start_day(
+                &self.logs_dir,
                 current_date,
+                settings.day_start,
                 new_state,
-            );
-            new_event_log.save().await?;
-            *event_log = new_event_log;
+                &event_log,
+            )
+            .await?;
 
             // Update current date in settings
             settings.current_date = current_date;
@@ -137,6 +313,26 @@ impl AppState {
             event_log.refresh_active().await?;
         }
 
+        if let Some(threshold) = settings.auto_break {
+            if event_log.auto_break(threshold) {
+                event_log.save().await?;
+
+                if send {
+                    self.send_event_log(&event_log).await;
+                }
+            }
+        }
+
+        // Archiving can take a while, so the new day doesn't wait on it
+        if new_day {
+            let retention_days = settings.raw_event_retention_days;
+
+            drop(event_log);
+            drop(settings);
+
+            self.archive_old_logs(current_date, retention_days).await?;
+        }
+
         Ok(injected_idle)
     }
 }
@@ -151,14 +347,23 @@ async fn clock_in(
         .await
         .map_err(|err| err.to_string())?;
 
+    let clock = state
+        .settings
+        .lock()
+        .await
+        .resolve_clock(clock)
+        .map_err(|err| err.to_string())?;
+
+    let notification_settings = state.notification_settings().await;
     let mut event_log = state.event_log.write().await;
 
-    event_log.add_event(timecard::Event::clock_in(clock));
+    let now = event_log.now();
+    event_log.add_event(timecard::Event::clock_in(now, clock));
     event_log.save().await.map_err(|err| err.to_string())?;
 
     state.send_event_log(&event_log).await;
     state
-        .update_notifications(&event_log)
+        .update_notifications(&notification_settings, &event_log)
         .await
         .map_err(|err| err.to_string())?;
 
@@ -175,14 +380,23 @@ async fn clock_out(
         .await
         .map_err(|err| err.to_string())?;
 
+    let clock = state
+        .settings
+        .lock()
+        .await
+        .resolve_clock(clock)
+        .map_err(|err| err.to_string())?;
+
+    let notification_settings = state.notification_settings().await;
     let mut event_log = state.event_log.write().await;
 
-    event_log.add_event(timecard::Event::clock_out(clock));
+    let now = event_log.now();
+    event_log.add_event(timecard::Event::clock_out(now, clock));
     event_log.save().await.map_err(|err| err.to_string())?;
 
     state.send_event_log(&event_log).await;
     state
-        .update_notifications(&event_log)
+        .update_notifications(&notification_settings, &event_log)
         .await
         .map_err(|err| err.to_string())?;
 
@@ -192,6 +406,192 @@ async fn clock_out(
 #[tauri::command]
 async fn set_tasks(
     tasks: BTreeSet<tasks::TaskID>,
+    weights: Option<BTreeMap<tasks::TaskID, u32>>,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state
+        .refresh_date(false, true, false)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let notification_settings = state.notification_settings().await;
+    let mut event_log = state.event_log.write().await;
+
+    let now = event_log.now();
+    let event = timecard::Event::tasks(now, tasks, weights.unwrap_or_default())
+        .map_err(|err| err.to_string())?;
+
+    event_log.add_event(event);
+    event_log.save().await.map_err(|err| err.to_string())?;
+
+    state.send_event_log(&event_log).await;
+    state
+        .update_notifications(&notification_settings, &event_log)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn start_pomodoro(
+    task: tasks::TaskID,
+    focus: std::time::Duration,
+    rest: std::time::Duration,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let mut pomodoro = state.pomodoro.lock().await;
+
+    state
+        .refresh_date(false, true, false)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let notification_settings = state.notification_settings().await;
+    let mut event_log = state.event_log.write().await;
+
+    if let Some(previous) = pomodoro.take() {
+        previous.stop(&mut event_log);
+    }
+
+    *pomodoro = Some(
+        pomodoro::Pomodoro::start(&mut event_log, task, focus, rest)
+            .map_err(|err| err.to_string())?,
+    );
+    event_log.save().await.map_err(|err| err.to_string())?;
+
+    state.send_event_log(&event_log).await;
+    state.send_pomodoro(&pomodoro).await;
+    state
+        .update_notifications(&notification_settings, &event_log)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_pomodoro(state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    let mut pomodoro = state.pomodoro.lock().await;
+
+    let Some(timer) = pomodoro.take() else {
+        return Ok(());
+    };
+
+    state
+        .refresh_date(false, true, false)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let notification_settings = state.notification_settings().await;
+    let mut event_log = state.event_log.write().await;
+
+    timer.stop(&mut event_log);
+    event_log.save().await.map_err(|err| err.to_string())?;
+
+    state.send_event_log(&event_log).await;
+    state.send_pomodoro(&pomodoro).await;
+    state
+        .update_notifications(&notification_settings, &event_log)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_pomodoro(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<Option<pomodoro::Pomodoro>, ()> {
+    Ok(state.pomodoro.lock().await.clone())
+}
+
+#[tauri::command]
+async fn resolve_idle(
+    span: timecard::IdleSpan,
+    resolution: timecard::IdleResolution,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state
+        .refresh_date(false, true, false)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let notification_settings = state.notification_settings().await;
+    let mut event_log = state.event_log.write().await;
+
+    event_log
+        .resolve_idle(span, resolution)
+        .map_err(|err| err.to_string())?;
+    event_log.save().await.map_err(|err| err.to_string())?;
+
+    state.send_event_log(&event_log).await;
+    state
+        .update_notifications(&notification_settings, &event_log)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn insert_event(
+    event: timecard::Event,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state
+        .refresh_date(false, true, false)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let notification_settings = state.notification_settings().await;
+    let mut event_log = state.event_log.write().await;
+
+    event_log
+        .insert_event(event)
+        .map_err(|err| err.to_string())?;
+    event_log.save().await.map_err(|err| err.to_string())?;
+
+    state.send_event_log(&event_log).await;
+    state
+        .update_notifications(&notification_settings, &event_log)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn delete_event(
+    event: timecard::Event,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    state
+        .refresh_date(false, true, false)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let notification_settings = state.notification_settings().await;
+    let mut event_log = state.event_log.write().await;
+
+    event_log
+        .remove_event(&event)
+        .map_err(|err| err.to_string())?;
+    event_log.save().await.map_err(|err| err.to_string())?;
+
+    state.send_event_log(&event_log).await;
+    state
+        .update_notifications(&notification_settings, &event_log)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn amend_event(
+    old: timecard::Event,
+    new: timecard::Event,
     state: tauri::State<'_, Arc<AppState>>,
 ) -> Result<(), String> {
     state
@@ -199,20 +599,207 @@ async fn set_tasks(
         .await
         .map_err(|err| err.to_string())?;
 
+    let notification_settings = state.notification_settings().await;
     let mut event_log = state.event_log.write().await;
 
-    event_log.add_event(timecard::Event::tasks(tasks));
+    event_log
+        .amend_event(&old, new)
+        .map_err(|err| err.to_string())?;
     event_log.save().await.map_err(|err| err.to_string())?;
 
     state.send_event_log(&event_log).await;
     state
-        .update_notifications(&event_log)
+        .update_notifications(&notification_settings, &event_log)
         .await
         .map_err(|err| err.to_string())?;
 
     Ok(())
 }
 
+#[tauri::command]
+async fn set_event_note(
+    event: timecard::Event,
+    note: Option<String>,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let mut event_log = state.event_log.write().await;
+
+    event_log
+        .set_event_note(&event, note)
+        .map_err(|err| err.to_string())?;
+    event_log.save().await.map_err(|err| err.to_string())?;
+
+    state.send_event_log(&event_log).await;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_day_note(
+    date: NaiveDate,
+    note: Option<String>,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let _archiving = state.archiving.lock().await;
+    let mut event_log = state.event_log.write().await;
+
+    history::set_day_note(&state.logs_dir, date, note, &mut event_log)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if date == event_log.date() {
+        state.send_event_log(&event_log).await;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_day_status(
+    date: NaiveDate,
+    status: timecard::DayStatus,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().await;
+
+        settings.schedule.set_status(date, status);
+        settings.save().await.map_err(|err| err.to_string())?;
+    }
+
+    let notification_settings = state.notification_settings().await;
+    let _archiving = state.archiving.lock().await;
+    let mut event_log = state.event_log.write().await;
+
+    history::set_day_status(&state.logs_dir, date, status, &mut event_log)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if date == event_log.date() {
+        state.send_event_log(&event_log).await;
+        state
+            .update_notifications(&notification_settings, &event_log)
+            .await
+            .map_err(|err| err.to_string())?;
+    } else {
+        *state.flex_bank.lock().await = None;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn add_manual_time(
+    date: NaiveDate,
+    time: Option<DateTime<FixedOffset>>,
+    task: tasks::TaskID,
+    duration: std::time::Duration,
+    counts_as_work: bool,
+    note: Option<String>,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let notification_settings = state.notification_settings().await;
+    let _archiving = state.archiving.lock().await;
+    let mut event_log = state.event_log.write().await;
+
+    history::edit_day(&state.logs_dir, date, &mut event_log, |event_log| {
+        event_log.add_manual_time(time, task, duration, counts_as_work, note)
+    })
+    .await
+    .map_err(|err| err.to_string())?;
+
+    if date == event_log.date() {
+        state.send_event_log(&event_log).await;
+        state
+            .update_notifications(&notification_settings, &event_log)
+            .await
+            .map_err(|err| err.to_string())?;
+    } else {
+        *state.flex_bank.lock().await = None;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_schedule(state: tauri::State<'_, Arc<AppState>>) -> Result<settings::Schedule, ()> {
+    Ok(state.settings.lock().await.schedule.clone())
+}
+
+#[tauri::command]
+async fn set_weekly_schedule(
+    weekly: [std::time::Duration; 7],
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().await;
+
+        settings.schedule.weekly = weekly;
+        settings.save().await.map_err(|err| err.to_string())?;
+    }
+
+    let notification_settings = state.notification_settings().await;
+    let event_log = state.event_log.read().await;
+    state
+        .update_notifications(&notification_settings, &event_log)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn set_schedule_exception(
+    date: NaiveDate,
+    exception: Option<settings::ScheduleException>,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<(), String> {
+    let status = {
+        let mut settings = state.settings.lock().await;
+
+        match exception {
+            Some(exception) => settings.schedule.exceptions.insert(date, exception),
+            None => settings.schedule.exceptions.remove(&date),
+        };
+        settings.save().await.map_err(|err| err.to_string())?;
+
+        settings.schedule.status_for(date)
+    };
+
+    let notification_settings = state.notification_settings().await;
+
+    // Keep the day's log in step with any day off
+    let _archiving = state.archiving.lock().await;
+    let mut event_log = state.event_log.write().await;
+
+    history::set_day_status(&state.logs_dir, date, status, &mut event_log)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if date == event_log.date() {
+        state.send_event_log(&event_log).await;
+    }
+
+    state
+        .update_notifications(&notification_settings, &event_log)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn get_categories(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<Vec<settings::CategorySettings>, ()> {
+    Ok(state.settings.lock().await.categories.clone())
+}
+
+#[tauri::command]
+async fn list_quarantined(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<Vec<repair::QuarantinedFile>, String> {
+    repair::list_quarantined(&repair::quarantine_dir(&state.logs_dir))
+        .await
+        .map_err(|err| err.to_string())
+}
+
 #[tauri::command]
 async fn get_current_timecard(
     state: tauri::State<'_, Arc<AppState>>,
@@ -221,6 +808,85 @@ async fn get_current_timecard(
     Ok(event_log.clone())
 }
 
+#[tauri::command]
+async fn get_task_elapsed(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<BTreeMap<tasks::TaskID, std::time::Duration>, ()> {
+    let event_log = state.event_log.read().await;
+    Ok(event_log.task_elapsed())
+}
+
+#[tauri::command]
+async fn check_timecard(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<Vec<consistency::Diagnostic>, ()> {
+    let event_log = state.event_log.read().await;
+    Ok(consistency::check(&event_log))
+}
+
+#[tauri::command]
+async fn get_history(
+    start: NaiveDate,
+    end: NaiveDate,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<history::History, String> {
+    let schedule = state.settings.lock().await.schedule.clone();
+    let event_log = state.event_log.read().await;
+
+    history::load_range(&state.logs_dir, start, end, &event_log, &schedule)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn get_period_summaries(
+    period: history::Period,
+    start: NaiveDate,
+    end: NaiveDate,
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<Vec<history::PeriodSummary>, String> {
+    let schedule = state.settings.lock().await.schedule.clone();
+    let event_log = state.event_log.read().await;
+
+    history::load_periods(&state.logs_dir, period, start, end, &event_log, &schedule)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn get_projection(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<timecard::Projection, ()> {
+    // Settings are never locked while the event log is, so copy them out first
+    let (schedule, lunch_target) = {
+        let settings = state.settings.lock().await;
+
+        (settings.schedule.clone(), settings.lunch_target)
+    };
+
+    let event_log = state.event_log.read().await;
+
+    Ok(event_log.projection(schedule.target_for(event_log.date()), lunch_target))
+}
+
+#[tauri::command]
+async fn get_flex_balance(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<history::FlexSummary, String> {
+    let (flex_start, schedule) = {
+        let settings = state.settings.lock().await;
+
+        (settings.flex_start, settings.schedule.clone())
+    };
+
+    let event_log = state.event_log.read().await;
+
+    state
+        .flex(flex_start, &schedule, &event_log)
+        .await
+        .map_err(|err| err.to_string())
+}
+
 #[tauri::command]
 async fn get_recents(state: tauri::State<'_, Arc<AppState>>) -> Result<tasks::Recents, ()> {
     Ok(state.task_manager.get_recents().await)
@@ -309,15 +975,30 @@ async fn background_loop(app_state: Arc<AppState>) {
             println!("error updating event log: {}", e);
         }
 
-        async_std::task::sleep(loop_time - start.elapsed()).await;
+        async_std::task::sleep(loop_time.saturating_sub(start.elapsed())).await;
+    }
+}
+
+async fn pomodoro_loop(app_state: Arc<AppState>) {
+    let loop_time = std::time::Duration::from_secs(1);
+
+    loop {
+        if let Err(e) = app_state.tick_pomodoro().await {
+            println!("error updating pomodoro: {}", e);
+        }
+
+        async_std::task::sleep(loop_time).await;
     }
 }
 
 async fn update_event_log(app_state: &AppState) -> Result<(), Box<dyn Error>> {
     {
+        let notification_settings = app_state.notification_settings().await;
         let event_log = app_state.event_log.read().await;
 
-        app_state.update_notifications(&event_log).await?;
+        app_state
+            .update_notifications(&notification_settings, &event_log)
+            .await?;
     }
 
     Ok(())
@@ -334,19 +1015,87 @@ fn main() {
     std::fs::create_dir_all(&logs_dir).expect("could not create timecard logs directory");
     std::fs::create_dir_all(&tasks_dir).expect("could not create tasks directory");
 
-    let current_date = Local::now().date_naive();
-    let log_file = log_file_for_date(&logs_dir, current_date);
+    let clock = clock::system();
 
-    let event_log = if log_file.exists() {
-        async_runtime::block_on(timecard::EventLog::load(log_file.into()))
-            .expect("couldn't load initial time card")
-    } else {
-        timecard::EventLog::new(log_file.into(), current_date, timecard::State::default())
+    let settings = async_runtime::block_on(settings::Settings::load_or_new(
+        config_file.clone().into(),
+        &*clock,
+    ))
+    .expect("error loading/initializing settings");
+
+    let current_date = timecard::workday_for(&clock.now(), settings.day_start);
+    let log_file = timecard::log_file_for_date(&logs_dir, current_date);
+
+    let quarantine_dir = repair::quarantine_dir(&logs_dir);
+
+    let mut warnings = async_runtime::block_on(compact_old_journals(
+        &logs_dir,
+        &log_file,
+        settings.day_start,
+        clock.clone(),
+    ))
+    .unwrap_or_else(|err| vec![format!("couldn't compact old time card journals: {}", err)]);
+
+    let archive_run = match async_runtime::block_on(archive::archive_old_logs(
+        &logs_dir,
+        current_date,
+        settings.raw_event_retention_days,
+        None,
+        clock.clone(),
+    )) {
+        Ok(run) => {
+            warnings.extend(run.warnings.iter().cloned());
+            Some(run)
+        }
+        Err(err) => {
+            println!("error archiving old logs: {}", err);
+            None
+        }
+    };
+
+    let event_log = match async_runtime::block_on(repair::load_or_repair(
+        log_file.clone().into(),
+        current_date,
+        settings.day_start,
+        clock.clone(),
+        &quarantine_dir,
+    )) {
+        Ok((event_log, current_warnings)) => {
+            warnings.extend(current_warnings);
+            event_log
+        }
+        Err(err) => {
+            // Set the log aside and start over under the usual name, so history still finds today
+            let moved = async_runtime::block_on(repair::quarantine_log(
+                log_file.as_path().into(),
+                &quarantine_dir,
+                &*clock,
+            ));
+
+            warnings.push(match moved {
+                Ok(moved) => format!(
+                    "couldn't load today's time card, so it was quarantined as {:?} and a new one was started: {}",
+                    moved, err
+                ),
+                Err(quarantine_err) => format!(
+                    "couldn't load today's time card: {}, and couldn't quarantine it: {}",
+                    err, quarantine_err
+                ),
+            });
+
+            timecard::EventLog::new(
+                log_file.into(),
+                current_date,
+                settings.day_start,
+                timecard::State::default(),
+                clock.clone(),
+            )
+        }
     };
 
-    let settings =
-        async_runtime::block_on(settings::Settings::load_or_new(config_file.clone().into()))
-            .expect("error loading/initializing settings");
+    for warning in &warnings {
+        println!("warning: {}", warning);
+    }
 
     let task_manager =
         async_runtime::block_on(tasks::TaskManager::load_or_new(tasks_dir.clone().into()))
@@ -354,14 +1103,24 @@ fn main() {
 
     let app_state = Arc::new(AppState {
         logs_dir,
+        clock: clock.clone(),
         event_log: RwLock::new(event_log),
         settings: Mutex::new(settings),
         app_handle: RwLock::new(None),
-        notifier: notifications::Notifier::new(),
+        notifier: notifications::Notifier::new(clock),
         task_manager,
+        flex_bank: Mutex::new(None),
+        pomodoro: Mutex::new(None),
+        archiving: Mutex::new(archive_run),
     });
 
     async_runtime::block_on(async {
+        if !warnings.is_empty() {
+            if let Err(err) = app_state.notifier.show_repair_warnings(&warnings).await {
+                println!("error showing repair warnings: {}", err);
+            }
+        }
+
         app_state
             .refresh_date(false, false, false)
             .await
@@ -381,7 +1140,29 @@ fn main() {
             clock_in,
             clock_out,
             set_tasks,
+            start_pomodoro,
+            stop_pomodoro,
+            get_pomodoro,
+            resolve_idle,
+            insert_event,
+            delete_event,
+            amend_event,
+            set_event_note,
+            set_day_note,
+            add_manual_time,
+            set_day_status,
+            get_categories,
+            get_schedule,
+            set_weekly_schedule,
+            set_schedule_exception,
+            list_quarantined,
             get_current_timecard,
+            get_task_elapsed,
+            check_timecard,
+            get_history,
+            get_period_summaries,
+            get_flex_balance,
+            get_projection,
             get_recents,
             get_tasks,
             put_task,
@@ -398,21 +1179,29 @@ fn main() {
 
     let app_state_background = app_state.clone();
     async_runtime::spawn(background_loop(app_state_background));
+    async_runtime::spawn(pomodoro_loop(app_state.clone()));
 
     wayland::listen_idle(move |idle| {
         let result = async_runtime::block_on(async {
             let injected_idle = app_state.refresh_date(false, !idle, false).await?;
+            let auto_break = app_state.settings.lock().await.auto_break;
 
             let mut event_log = app_state.event_log.write().await;
 
+            let now = event_log.now();
+
             if idle {
                 if !injected_idle {
-                    event_log.add_event(timecard::Event::idle());
+                    event_log.add_event(timecard::Event::idle(now));
                     println!("idle");
                 }
             } else {
-                event_log.add_event(timecard::Event::active());
+                event_log.add_event(timecard::Event::active(now));
                 println!("active");
+
+                if let Some(threshold) = auto_break {
+                    event_log.auto_break(threshold);
+                }
             }
 
             event_log.save().await?;
@@ -429,11 +1218,52 @@ fn main() {
     app.run(|_, _| {});
 }
 
-fn log_file_for_date(logs_dir: &Path, date: NaiveDate) -> PathBuf {
-    logs_dir.join(format!(
-        "{}-{}-{}.log.json",
-        date.year(),
-        date.month(),
-        date.day()
-    ))
+// Journals are normally compacted at rollover, but not if the app wasn't running at the time
+async fn compact_old_journals(
+    logs_dir: &Path,
+    current_log_file: &Path,
+    day_start: NaiveTime,
+    clock: Arc<dyn clock::Clock>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut warnings = Vec::new();
+
+    for entry in std::fs::read_dir(logs_dir)? {
+        let path = entry?.path();
+
+        if path.extension() != Some("jsonl".as_ref()) {
+            continue;
+        }
+
+        let log_file = path.with_extension("json");
+
+        if log_file != current_log_file {
+            let Some(date) = timecard::date_for_log_file(&log_file) else {
+                println!("skipping {:?}, it isn't named after a date", path);
+                continue;
+            };
+
+            let result = repair::load_or_repair(
+                log_file.clone().into(),
+                date,
+                day_start,
+                clock.clone(),
+                &repair::quarantine_dir(logs_dir),
+            )
+            .await;
+
+            // Leave anything we can't handle for next time rather than giving up on the rest
+            match result {
+                Ok((mut event_log, log_warnings)) => {
+                    warnings.extend(log_warnings);
+
+                    if let Err(err) = event_log.compact().await {
+                        warnings.push(format!("couldn't compact {:?}: {}", log_file, err));
+                    }
+                }
+                Err(err) => warnings.push(format!("couldn't load {:?}: {}", log_file, err)),
+            }
+        }
+    }
+
+    Ok(warnings)
 }
@@ -1,5 +1,6 @@
-use std::error::Error;
+use std::{collections::BTreeMap, error::Error, sync::Arc};
 
+use crate::{clock::Clock, pomodoro::Phase};
 use async_std::sync::Mutex;
 use chrono::prelude::*;
 use notify_rust::{Hint, Notification, NotificationHandle, Timeout};
@@ -14,6 +15,7 @@ struct OverNotification {
 impl OverNotification {
     async fn new<F>(
         accumulated: std::time::Duration,
+        now: chrono::DateTime<Local>,
         render_func: F,
     ) -> Result<OverNotification, Box<dyn Error>>
     where
@@ -32,13 +34,13 @@ impl OverNotification {
         Ok(OverNotification {
             handle,
             render: Box::new(render_func),
-            since: Local::now(),
+            since: now,
             accumulated,
         })
     }
 
-    async fn refresh(&self) -> Result<(), Box<dyn Error>> {
-        let total = self.accumulated + (Local::now() - self.since).to_std().unwrap();
+    async fn refresh(&self, now: chrono::DateTime<Local>) -> Result<(), Box<dyn Error>> {
+        let total = self.accumulated + (now - self.since).to_std().unwrap_or_default();
         let (summary, body) = (self.render)(total);
 
         Notification::new()
@@ -55,37 +57,58 @@ impl OverNotification {
 }
 
 pub struct Notifier {
+    clock: Arc<dyn Clock>,
+
     overtime: Mutex<Option<OverNotification>>,
     long_lunch: Mutex<Option<OverNotification>>,
     long_break: Mutex<Option<OverNotification>>,
+    long_categories: Mutex<BTreeMap<String, OverNotification>>,
+    time_left: Mutex<Option<NotificationHandle>>,
 }
 
 impl Notifier {
-    pub fn new() -> Notifier {
+    pub fn new(clock: Arc<dyn Clock>) -> Notifier {
         Notifier {
+            clock,
+
             overtime: Mutex::new(None),
             long_lunch: Mutex::new(None),
             long_break: Mutex::new(None),
+            long_categories: Mutex::new(BTreeMap::new()),
+            time_left: Mutex::new(None),
         }
     }
 
-    pub async fn show_overtime(&self, over: std::time::Duration) -> Result<(), Box<dyn Error>> {
+    // `week_flex` is the flex time for the week apart from today's overtime, which keeps growing
+    pub async fn show_overtime(
+        &self,
+        over: std::time::Duration,
+        week_flex: chrono::Duration,
+    ) -> Result<(), Box<dyn Error>> {
         let mut overtime = self.overtime.lock().await;
+        let now = self.clock.now();
+
+        let render = move |dur| {
+            let week = week_flex
+                + chrono::Duration::from_std(dur).unwrap_or_else(|_| chrono::Duration::zero());
+
+            (
+                "Working overtime".to_owned(),
+                format!(
+                    "{} overtime worked today, {} this week",
+                    format_duration_minutes(dur),
+                    format_flex(week)
+                ),
+            )
+        };
 
         if let Some(overtime) = &mut *overtime {
             overtime.accumulated = over;
-            overtime.since = Local::now();
-            overtime.refresh().await?;
+            overtime.since = now;
+            overtime.render = Box::new(render);
+            overtime.refresh(now).await?;
         } else {
-            *overtime = Some(
-                OverNotification::new(over, |dur| {
-                    (
-                        "Working overtime".to_owned(),
-                        format!("{} overtime worked today", format_duration_minutes(dur)),
-                    )
-                })
-                .await?,
-            )
+            *overtime = Some(OverNotification::new(over, now, render).await?)
         }
 
         Ok(())
@@ -101,14 +124,15 @@ impl Notifier {
 
     pub async fn show_long_lunch(&self, over: std::time::Duration) -> Result<(), Box<dyn Error>> {
         let mut long_lunch = self.long_lunch.lock().await;
+        let now = self.clock.now();
 
         if let Some(long_lunch) = &mut *long_lunch {
             long_lunch.accumulated = over;
-            long_lunch.since = Local::now();
-            long_lunch.refresh().await?;
+            long_lunch.since = now;
+            long_lunch.refresh(now).await?;
         } else {
             *long_lunch = Some(
-                OverNotification::new(over, |dur| {
+                OverNotification::new(over, now, |dur| {
                     (
                         "Long lunch".to_owned(),
                         format!("Over by {} for lunch today", format_duration_minutes(dur)),
@@ -131,14 +155,15 @@ impl Notifier {
 
     pub async fn show_long_break(&self, over: std::time::Duration) -> Result<(), Box<dyn Error>> {
         let mut long_break = self.long_break.lock().await;
+        let now = self.clock.now();
 
         if let Some(long_break) = &mut *long_break {
             long_break.accumulated = over;
-            long_break.since = Local::now();
-            long_break.refresh().await?;
+            long_break.since = now;
+            long_break.refresh(now).await?;
         } else {
             *long_break = Some(
-                OverNotification::new(over, |dur| {
+                OverNotification::new(over, now, |dur| {
                     (
                         "Over break time".to_owned(),
                         format!(
@@ -161,6 +186,156 @@ impl Notifier {
             notification.handle.close();
         }
     }
+
+    pub async fn show_long_category(
+        &self,
+        name: &str,
+        over: std::time::Duration,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut long_categories = self.long_categories.lock().await;
+        let now = self.clock.now();
+
+        if let Some(long_category) = long_categories.get_mut(name) {
+            long_category.accumulated = over;
+            long_category.since = now;
+            long_category.refresh(now).await?;
+        } else {
+            let owned_name = name.to_owned();
+
+            long_categories.insert(
+                name.to_owned(),
+                OverNotification::new(over, now, move |dur| {
+                    (
+                        format!("Over {} time", owned_name),
+                        format!(
+                            "Over by {} for {} today",
+                            format_duration_minutes(dur),
+                            owned_name
+                        ),
+                    )
+                })
+                .await?,
+            );
+        }
+
+        Ok(())
+    }
+
+    pub async fn clear_long_category(&self, name: &str) {
+        let notification = self.long_categories.lock().await.remove(name);
+
+        if let Some(notification) = notification {
+            notification.handle.close();
+        }
+    }
+
+    pub async fn show_time_left(
+        &self,
+        work_left: std::time::Duration,
+        lunch_left: std::time::Duration,
+        end: chrono::DateTime<FixedOffset>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut time_left = self.time_left.lock().await;
+
+        let mut body = format!(
+            "{} of work left, done around {}",
+            format_duration_minutes(work_left),
+            end.with_timezone(&Local).format("%-I:%M %p")
+        );
+
+        if !lunch_left.is_zero() {
+            body += &format!(" after {} for lunch", format_duration_minutes(lunch_left));
+        }
+
+        let mut notification = Notification::new();
+        notification
+            .summary("Time left today")
+            .body(&body)
+            .hint(Hint::Resident(true))
+            .timeout(Timeout::Never);
+
+        // Update the notification in place once it's up
+        if let Some(handle) = &*time_left {
+            notification.id(handle.id());
+        }
+
+        *time_left = Some(notification.show_async().await?);
+
+        Ok(())
+    }
+
+    pub async fn clear_time_left(&self) {
+        let notification = self.time_left.lock().await.take();
+
+        if let Some(notification) = notification {
+            notification.close();
+        }
+    }
+
+    pub async fn show_auto_clock_out(
+        &self,
+        time: chrono::DateTime<FixedOffset>,
+    ) -> Result<(), Box<dyn Error>> {
+        Notification::new()
+            .summary("Clocked out automatically")
+            .body(&format!(
+                "You've been idle since {}, so you were clocked out at that time",
+                time.with_timezone(&Local).format("%-I:%M %p")
+            ))
+            .show_async()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn show_pomodoro(
+        &self,
+        ended: Phase,
+        next: std::time::Duration,
+    ) -> Result<(), Box<dyn Error>> {
+        let (summary, body) = match ended {
+            Phase::Focus => (
+                "Focus interval done",
+                format!("Take a {} break", format_duration_minutes(next)),
+            ),
+            Phase::Break => (
+                "Break's over",
+                format!("Back to {} of focus", format_duration_minutes(next)),
+            ),
+        };
+
+        Notification::new()
+            .summary(summary)
+            .body(&body)
+            .show_async()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn show_repair_warnings(&self, warnings: &[String]) -> Result<(), Box<dyn Error>> {
+        Notification::new()
+            .summary("Time card repaired")
+            .body(&warnings.join("\n"))
+            .show_async()
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn format_flex(flex: chrono::Duration) -> String {
+    let sign = if flex < chrono::Duration::zero() {
+        "-"
+    } else {
+        "+"
+    };
+
+    format!(
+        "{}{}",
+        sign,
+        format_duration_minutes(flex.abs().to_std().unwrap_or_default())
+    )
 }
 
 fn format_duration_minutes(dur: std::time::Duration) -> String {
@@ -0,0 +1,277 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+};
+
+use chrono::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    tasks::TaskID,
+    timecard::{Category, ClockType, Event, EventLog},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Phase {
+    Focus,
+    Break,
+}
+
+// A focus timer that tracks one task, then takes a break, over and over until it's stopped
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pomodoro {
+    pub task: TaskID,
+    pub focus: std::time::Duration,
+    pub rest: std::time::Duration,
+
+    pub phase: Phase,
+    pub phase_end: DateTime<FixedOffset>,
+}
+
+impl Pomodoro {
+    // Starts focusing on the task, clocking in for the day and out of anything that would pause the
+    // task if needed
+    pub fn start(
+        event_log: &mut EventLog,
+        task: TaskID,
+        focus: std::time::Duration,
+        rest: std::time::Duration,
+    ) -> Result<Pomodoro, Box<dyn Error>> {
+        let max = std::time::Duration::from_secs(24 * 60 * 60);
+
+        if focus.is_zero() || focus > max || rest.is_zero() || rest > max {
+            return Err("focus and break lengths must be between zero and 24 hours".into());
+        }
+
+        let now = event_log.now();
+        let tasks = Event::tasks(now, BTreeSet::from([task]), BTreeMap::new())?;
+
+        let state = event_log.current_state();
+        let mut paused_by = Vec::new();
+
+        if state.on_break.active() {
+            paused_by.push(ClockType::Break);
+        }
+
+        if state.on_lunch.active() {
+            paused_by.push(ClockType::Lunch);
+        }
+
+        for (name, category) in &state.categories {
+            if category.time.active() && category.pauses_tasks {
+                paused_by.push(ClockType::Category(Category {
+                    name: name.clone(),
+                    counts_as_work: category.counts_as_work,
+                    pauses_tasks: category.pauses_tasks,
+                }));
+            }
+        }
+
+        for clock in paused_by {
+            event_log.add_event(Event::clock_out(now, clock));
+        }
+
+        if !event_log.current_state().working.active() {
+            event_log.add_event(Event::clock_in(now, ClockType::Day));
+        }
+
+        event_log.add_event(tasks);
+
+        Ok(Pomodoro {
+            task,
+            focus,
+            rest,
+
+            phase: Phase::Focus,
+            phase_end: after(now, focus),
+        })
+    }
+
+    // Moves on to the next phase once the current one is over, returning the phase that ended
+    pub fn advance(&mut self, event_log: &mut EventLog) -> Option<Phase> {
+        let now = event_log.now();
+
+        if now < self.phase_end {
+            return None;
+        }
+
+        let ended = self.phase;
+        let state = event_log.current_state();
+        let working = state.working.active();
+        let on_task = state.tasks.tracks(&self.task);
+        let on_break = state.on_break.active();
+
+        match self.phase {
+            Phase::Focus => {
+                // Intervals only count if we were still clocked in on the task by the end of them
+                if working && on_task {
+                    event_log.complete_pomodoro(self.task);
+                }
+
+                // A break that was already started by hand just carries on
+                if working && !on_break {
+                    event_log.add_event(Event::clock_in(now, ClockType::Break));
+                }
+
+                self.phase = Phase::Break;
+                self.phase_end = after(now, self.rest);
+            }
+            Phase::Break => {
+                self.stop_break(event_log);
+
+                self.phase = Phase::Focus;
+                self.phase_end = after(now, self.focus);
+            }
+        }
+
+        Some(ended)
+    }
+
+    pub fn stop(self, event_log: &mut EventLog) {
+        if self.phase == Phase::Break {
+            self.stop_break(event_log);
+        }
+    }
+
+    fn stop_break(&self, event_log: &mut EventLog) {
+        if event_log.current_state().on_break.active() {
+            let now = event_log.now();
+            event_log.add_event(Event::clock_out(now, ClockType::Break));
+        }
+    }
+
+    // How long the phase after this one lasts
+    pub fn next_length(&self) -> std::time::Duration {
+        match self.phase {
+            Phase::Focus => self.rest,
+            Phase::Break => self.focus,
+        }
+    }
+}
+
+// Lengths are checked when starting, so they always fit
+fn after(time: DateTime<FixedOffset>, length: std::time::Duration) -> DateTime<FixedOffset> {
+    time + chrono::Duration::from_std(length).unwrap_or_else(|_| chrono::Duration::zero())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::test_util::{local, minutes, new_event_log, task};
+
+    #[test]
+    fn pomodoro_cycles_between_focus_and_break() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+        let task = task(3);
+
+        let mut pomodoro = Pomodoro::start(&mut event_log, task, minutes(25), minutes(5)).unwrap();
+        assert!(event_log.current_state().working.active());
+        assert!(event_log
+            .events()
+            .iter()
+            .any(|event| matches!(event, Event::Tasks { tasks, .. } if tasks.contains(&task))));
+
+        clock.advance(chrono::Duration::minutes(20));
+        assert_eq!(pomodoro.advance(&mut event_log), None);
+
+        clock.advance(chrono::Duration::minutes(5));
+        assert_eq!(pomodoro.advance(&mut event_log), Some(Phase::Focus));
+        assert_eq!(pomodoro.phase, Phase::Break);
+        assert!(event_log.current_state().on_break.active());
+        assert_eq!(event_log.pomodoros().get(&task), Some(&1));
+
+        clock.advance(chrono::Duration::minutes(5));
+        assert_eq!(pomodoro.advance(&mut event_log), Some(Phase::Break));
+        assert_eq!(pomodoro.phase, Phase::Focus);
+        assert!(!event_log.current_state().on_break.active());
+
+        clock.advance(chrono::Duration::minutes(25));
+        pomodoro.advance(&mut event_log);
+        assert_eq!(event_log.pomodoros().get(&task), Some(&2));
+
+        // Stopping partway through a break ends it
+        clock.advance(chrono::Duration::minutes(2));
+        pomodoro.stop(&mut event_log);
+        assert!(!event_log.current_state().on_break.active());
+        assert!(event_log.current_state().working.active());
+    }
+
+    #[test]
+    fn pomodoro_ends_lunch_and_pausing_categories() {
+        let clock = FakeClock::new(local(15, 12, 0));
+        let mut event_log = new_event_log(&clock);
+        let task = task(3);
+        let meeting = ClockType::Category(Category {
+            name: "Meeting".to_owned(),
+            counts_as_work: true,
+            pauses_tasks: true,
+        });
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.add_event(Event::clock_in(event_log.now(), meeting));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Lunch));
+
+        clock.advance(chrono::Duration::minutes(30));
+        let mut pomodoro = Pomodoro::start(&mut event_log, task, minutes(25), minutes(5)).unwrap();
+
+        let state = event_log.current_state();
+        assert!(!state.on_lunch.active());
+        assert!(!state.categories["Meeting"].time.active());
+
+        // The whole interval went to the task
+        clock.advance(chrono::Duration::minutes(25));
+        pomodoro.advance(&mut event_log);
+        assert_eq!(event_log.task_elapsed().get(&task), Some(&minutes(25)));
+        assert_eq!(event_log.elapsed().lunch_time, minutes(30));
+    }
+
+    #[test]
+    fn pomodoro_only_counts_focus_on_its_task() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        let mut pomodoro =
+            Pomodoro::start(&mut event_log, task(3), minutes(25), minutes(5)).unwrap();
+
+        // Switched to another task and took a break early
+        clock.advance(chrono::Duration::minutes(10));
+        let now = event_log.now();
+        event_log.add_event(Event::tasks(now, BTreeSet::from([task(4)]), BTreeMap::new()).unwrap());
+        clock.advance(chrono::Duration::minutes(10));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Break));
+
+        clock.advance(chrono::Duration::minutes(5));
+        assert_eq!(pomodoro.advance(&mut event_log), Some(Phase::Focus));
+        assert!(event_log.pomodoros().is_empty());
+        assert!(event_log.current_state().on_break.active());
+
+        let break_clock_ins = event_log
+            .events()
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    Event::ClockIn {
+                        clock: ClockType::Break,
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(break_clock_ins, 1);
+    }
+
+    #[test]
+    fn pomodoro_lengths_are_checked() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+        let task = task(3);
+
+        assert!(Pomodoro::start(&mut event_log, task, minutes(0), minutes(5)).is_err());
+        assert!(Pomodoro::start(&mut event_log, task, minutes(25), minutes(25 * 60)).is_err());
+        assert!(event_log.events().is_empty());
+    }
+}
@@ -0,0 +1,523 @@
+use std::{collections::BTreeSet, error::Error, sync::Arc};
+
+use async_std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use chrono::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    clock::Clock,
+    consistency::{self, Diagnostic},
+    journal::{self, JournalEntry},
+    schema::{self, Versioned},
+    timecard::{Event, EventLog, State},
+};
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuarantinedFile {
+    pub name: String,
+    pub size: u64,
+}
+
+pub fn quarantine_dir(logs_dir: &std::path::Path) -> PathBuf {
+    logs_dir.join("quarantine").into()
+}
+
+// Loads an event log, falling back to recovering what it can from the files if they can't be
+// loaded, and rebuilding the current state if it doesn't match the events. Anything that had to be
+// fixed gets returned as warnings.
+pub async fn load_or_repair(
+    filename: PathBuf,
+    date: NaiveDate,
+    day_start: NaiveTime,
+    clock: Arc<dyn Clock>,
+    quarantine_dir: &Path,
+) -> Result<(EventLog, Vec<String>), Box<dyn Error>> {
+    let mut warnings = Vec::new();
+
+    let mut event_log =
+        match EventLog::load_or_new(filename.clone(), date, day_start, clock.clone()).await {
+            Ok(event_log) => event_log,
+            Err(err) => {
+                check_version(&filename).await?;
+                warnings.push(format!("couldn't load {:?}: {}", filename, err));
+
+                let event_log = recover(
+                    filename,
+                    date,
+                    day_start,
+                    clock,
+                    quarantine_dir,
+                    &mut warnings,
+                )
+                .await?;
+
+                warnings.push(format!(
+                    "recovered {} events for {}",
+                    event_log.events().len(),
+                    date
+                ));
+
+                event_log
+            }
+        };
+
+    let mismatched = consistency::check(&event_log)
+        .iter()
+        .any(|diagnostic| matches!(diagnostic, Diagnostic::StateMismatch { .. }));
+
+    if mismatched {
+        event_log.replay();
+        warnings.push(format!(
+            "rebuilt the state for {} from its events since they didn't match",
+            date
+        ));
+    }
+
+    Ok((event_log, warnings))
+}
+
+// Logs written by a newer version of the app aren't damaged, just unreadable by this one, so they
+// get reported rather than quarantined
+pub async fn check_version(filename: &Path) -> Result<(), Box<dyn Error>> {
+    let journal_filename = journal::filename_for(filename);
+
+    let (file, buf) = if journal_filename.exists().await {
+        let buf = fs::read(&journal_filename).await?;
+        let first_line = buf
+            .split(|&b| b == b'\n')
+            .next()
+            .unwrap_or_default()
+            .to_vec();
+
+        (journal_filename, first_line)
+    } else if filename.exists().await {
+        (filename.to_owned(), fs::read(filename).await?)
+    } else {
+        return Ok(());
+    };
+
+    let newer = serde_json::from_slice(&buf)
+        .ok()
+        .and_then(|json| schema::newer_version::<EventLog>(&json));
+
+    match newer {
+        Some(version) => Err(format!(
+            "{:?} is from version {} of the time card format, but only up to version {} is supported",
+            file,
+            version,
+            EventLog::version()
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
+
+// Moves the log's files out of the way and builds a new log from whatever could be read out of them
+async fn recover(
+    filename: PathBuf,
+    date: NaiveDate,
+    day_start: NaiveTime,
+    clock: Arc<dyn Clock>,
+    quarantine_dir: &Path,
+    warnings: &mut Vec<String>,
+) -> Result<EventLog, Box<dyn Error>> {
+    let mut initial_state = None;
+    let mut events = BTreeSet::new();
+
+    let journal_filename = journal::filename_for(&filename);
+
+    if journal_filename.exists().await {
+        let (journal_state, journal_events) = salvage_journal(&fs::read(&journal_filename).await?);
+
+        initial_state = journal_state;
+        events.extend(journal_events);
+
+        let moved = quarantine(&journal_filename, quarantine_dir, &*clock).await?;
+        warnings.push(format!("quarantined {:?}", moved));
+    }
+
+    if filename.exists().await {
+        let (log_state, log_events) = salvage_log(&fs::read(&filename).await?);
+
+        initial_state = initial_state.or(log_state);
+        events.extend(log_events);
+
+        let moved = quarantine(&filename, quarantine_dir, &*clock).await?;
+        warnings.push(format!("quarantined {:?}", moved));
+    }
+
+    if initial_state.is_none() {
+        warnings.push(format!(
+            "couldn't recover the starting state for {}, so it starts clocked out",
+            date
+        ));
+    }
+
+    let mut event_log = EventLog::recovered(
+        filename,
+        date,
+        day_start,
+        initial_state.unwrap_or_default(),
+        events,
+        clock,
+    );
+    event_log.save().await?;
+
+    Ok(event_log)
+}
+
+fn salvage_journal(buf: &[u8]) -> (Option<State>, Vec<Event>) {
+    let mut initial_state = None;
+    let mut events = Vec::new();
+
+    for line in buf.split(|&b| b == b'\n') {
+        match serde_json::from_slice(line) {
+            // Each snapshot holds every event up to that point
+            Ok(JournalEntry::Snapshot(snapshot)) => {
+                initial_state = Some(snapshot.initial_state().clone());
+                events = snapshot.events().iter().cloned().collect();
+            }
+            Ok(JournalEntry::Event(event)) => events.push(event),
+            Ok(JournalEntry::ActiveUntil { .. }) => {}
+            // A damaged snapshot might still have most of its events intact
+            Err(_) => {
+                let (snapshot_state, snapshot_events) = salvage_log(line);
+
+                if snapshot_state.is_some() {
+                    initial_state = snapshot_state;
+                    events = snapshot_events;
+                }
+            }
+        }
+    }
+
+    (initial_state, events)
+}
+
+fn salvage_log(buf: &[u8]) -> (Option<State>, Vec<Event>) {
+    if let Ok(Value::Object(mut json)) = serde_json::from_slice(buf) {
+        let initial_state = json
+            .remove("initialState")
+            .and_then(|state| serde_json::from_value(state).ok());
+
+        let events = match json.remove("events") {
+            Some(Value::Array(events)) => events
+                .into_iter()
+                .filter_map(|event| serde_json::from_value(event).ok())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        return (initial_state, events);
+    }
+
+    // The file is cut off or mangled, so look for the pieces we need and parse as much as we can
+    let initial_state = find_after(buf, b"\"initialState\":")
+        .and_then(|rest| parse_sequence::<State>(rest).into_iter().next());
+    let events = find_after(buf, b"\"events\":[")
+        .map(parse_sequence::<Event>)
+        .unwrap_or_default();
+
+    (initial_state, events)
+}
+
+fn find_after<'a>(buf: &'a [u8], needle: &[u8]) -> Option<&'a [u8]> {
+    buf.windows(needle.len())
+        .position(|window| window == needle)
+        .map(|i| &buf[i + needle.len()..])
+}
+
+// Parses comma-separated values until one fails to parse
+fn parse_sequence<T: DeserializeOwned>(mut buf: &[u8]) -> Vec<T> {
+    let mut values = Vec::new();
+
+    loop {
+        let start = buf
+            .iter()
+            .position(|&b| !b.is_ascii_whitespace() && b != b',')
+            .unwrap_or(buf.len());
+        buf = &buf[start..];
+
+        let mut stream = serde_json::Deserializer::from_slice(buf).into_iter::<T>();
+
+        match stream.next() {
+            Some(Ok(value)) => {
+                values.push(value);
+                buf = &buf[stream.byte_offset()..];
+            }
+            _ => break,
+        }
+    }
+
+    values
+}
+
+pub async fn quarantine(
+    filename: &Path,
+    quarantine_dir: &Path,
+    clock: &dyn Clock,
+) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(quarantine_dir).await?;
+
+    let name = filename
+        .file_name()
+        .ok_or("can't quarantine a path without a file name")?
+        .to_string_lossy();
+    let moved = quarantine_dir.join(format!("{}.{}", name, clock.now().format("%Y%m%dT%H%M%S")));
+
+    fs::rename(filename, &moved).await?;
+
+    Ok(moved)
+}
+
+// Moves all of a log's files out of the way so a new log can be started under its name
+pub async fn quarantine_log(
+    filename: &Path,
+    quarantine_dir: &Path,
+    clock: &dyn Clock,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut moved = Vec::new();
+
+    for file in [journal::filename_for(filename), filename.to_owned()] {
+        if file.exists().await {
+            moved.push(quarantine(&file, quarantine_dir, clock).await?);
+        }
+    }
+
+    Ok(moved)
+}
+
+pub async fn list_quarantined(
+    quarantine_dir: &Path,
+) -> Result<Vec<QuarantinedFile>, Box<dyn Error>> {
+    let mut files = Vec::new();
+
+    if !quarantine_dir.exists().await {
+        return Ok(files);
+    }
+
+    for entry in std::fs::read_dir(quarantine_dir)? {
+        let entry = entry?;
+
+        files.push(QuarantinedFile {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            size: entry.metadata()?.len(),
+        });
+    }
+
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::test_util::{event_log_in, local, temp_dir};
+    use crate::timecard::ClockType;
+
+    // Writes a compacted log where the day was started and then a break was taken
+    async fn write_event_log(dir: &Path, clock: &Arc<FakeClock>) -> PathBuf {
+        let filename = dir.join("2023-11-15.log.json");
+        let mut event_log = event_log_in(dir, clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.save().await.unwrap();
+
+        clock.advance(chrono::Duration::minutes(30));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Break));
+        event_log.save().await.unwrap();
+
+        filename
+    }
+
+    async fn repair(dir: &Path, clock: &Arc<FakeClock>) -> (EventLog, Vec<String>) {
+        load_or_repair(
+            dir.join("2023-11-15.log.json"),
+            clock.now().date_naive(),
+            NaiveTime::MIN,
+            clock.clone(),
+            &quarantine_dir(dir.as_ref()),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn truncated_log_is_salvaged() {
+        async_std::task::block_on(async {
+            let dir = PathBuf::from(temp_dir("repair-truncated"));
+            let clock = FakeClock::new(local(15, 9, 0));
+
+            let filename = write_event_log(&dir, &clock).await;
+            let mut event_log = EventLog::load(filename.clone(), clock.clone())
+                .await
+                .unwrap();
+            event_log.compact().await.unwrap();
+
+            // Cut the file off partway through the break event
+            let json = fs::read(&filename).await.unwrap();
+            let cut = json.windows(7).rposition(|w| w == b"\"Break\"").unwrap();
+            fs::write(&filename, &json[..cut]).await.unwrap();
+
+            let (repaired, warnings) = repair(&dir, &clock).await;
+
+            assert!(!warnings.is_empty());
+            assert_eq!(repaired.events().len(), 1);
+            assert!(repaired.current_state().working.active());
+            assert!(!repaired.current_state().on_break.active());
+
+            // The repaired log is saved in place of the quarantined one
+            let loaded = EventLog::load(filename, clock.clone()).await.unwrap();
+            assert_eq!(loaded.events().len(), 1);
+
+            let quarantined = list_quarantined(&quarantine_dir(dir.as_ref()))
+                .await
+                .unwrap();
+            assert_eq!(quarantined.len(), 1);
+            assert!(quarantined[0].name.starts_with("2023-11-15.log.json."));
+            assert_eq!(quarantined[0].size, cut as u64);
+
+            fs::remove_dir_all(&dir).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn corrupt_journal_lines_are_skipped() {
+        async_std::task::block_on(async {
+            let dir = PathBuf::from(temp_dir("repair-journal"));
+            let clock = FakeClock::new(local(15, 9, 0));
+
+            let filename = write_event_log(&dir, &clock).await;
+            let journal_filename = journal::filename_for(&filename);
+
+            // Mangle the break event, with an entry after it so it isn't just a truncated append
+            let journal = fs::read_to_string(&journal_filename).await.unwrap();
+            let mut lines: Vec<&str> = journal.lines().collect();
+            assert_eq!(lines.len(), 2);
+            lines[1] = "{\"entry\":\"Event\",\"type\":";
+            lines.push("{\"entry\":\"ActiveUntil\",\"time\":null}");
+            fs::write(&journal_filename, lines.join("\n"))
+                .await
+                .unwrap();
+
+            assert!(EventLog::load(filename.clone(), clock.clone())
+                .await
+                .is_err());
+
+            let (repaired, warnings) = repair(&dir, &clock).await;
+
+            assert!(!warnings.is_empty());
+            assert_eq!(repaired.events().len(), 1);
+            assert!(repaired.current_state().working.active());
+
+            let loaded = EventLog::load(filename, clock.clone()).await.unwrap();
+            assert_eq!(loaded.events().len(), 1);
+
+            let quarantined = list_quarantined(&quarantine_dir(dir.as_ref()))
+                .await
+                .unwrap();
+            assert_eq!(quarantined.len(), 1);
+            assert!(quarantined[0].name.starts_with("2023-11-15.log.jsonl."));
+
+            fs::remove_dir_all(&dir).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn newer_versions_are_left_alone() {
+        async_std::task::block_on(async {
+            let dir = PathBuf::from(temp_dir("repair-newer"));
+            let clock = FakeClock::new(local(15, 9, 0));
+
+            let filename = write_event_log(&dir, &clock).await;
+            let journal_filename = journal::filename_for(&filename);
+
+            let journal = fs::read_to_string(&journal_filename).await.unwrap();
+            let newer = journal.replacen(
+                &format!("\"version\":{}", EventLog::version()),
+                &format!("\"version\":{}", EventLog::version() + 1),
+                1,
+            );
+            assert_ne!(newer, journal);
+            fs::write(&journal_filename, &newer).await.unwrap();
+
+            let err = load_or_repair(
+                filename.clone(),
+                clock.now().date_naive(),
+                NaiveTime::MIN,
+                clock.clone(),
+                &quarantine_dir(dir.as_ref()),
+            )
+            .await
+            .err()
+            .unwrap();
+            assert!(err.to_string().contains("only up to version"));
+
+            // Nothing was moved or rewritten
+            assert_eq!(fs::read_to_string(&journal_filename).await.unwrap(), newer);
+            assert!(list_quarantined(&quarantine_dir(dir.as_ref()))
+                .await
+                .unwrap()
+                .is_empty());
+
+            // Setting the log aside to start over takes all of its files
+            let moved = quarantine_log(&filename, &quarantine_dir(dir.as_ref()), &*clock)
+                .await
+                .unwrap();
+            assert!(!moved.is_empty());
+            assert!(!journal_filename.exists().await);
+            assert!(!filename.exists().await);
+            assert_eq!(
+                list_quarantined(&quarantine_dir(dir.as_ref()))
+                    .await
+                    .unwrap()
+                    .len(),
+                moved.len()
+            );
+
+            fs::remove_dir_all(&dir).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn mismatched_state_is_rebuilt() {
+        async_std::task::block_on(async {
+            let dir = PathBuf::from(temp_dir("repair-mismatch"));
+            let clock = FakeClock::new(local(15, 9, 0));
+
+            let filename = write_event_log(&dir, &clock).await;
+            let mut event_log = EventLog::load(filename.clone(), clock.clone())
+                .await
+                .unwrap();
+            event_log.compact().await.unwrap();
+
+            let mut json: Value =
+                serde_json::from_slice(&fs::read(&filename).await.unwrap()).unwrap();
+            json["currentState"] = serde_json::to_value(State::default()).unwrap();
+            fs::write(&filename, serde_json::to_vec(&json).unwrap())
+                .await
+                .unwrap();
+
+            let (repaired, warnings) = repair(&dir, &clock).await;
+
+            assert_eq!(warnings.len(), 1);
+            assert_eq!(repaired.events().len(), 2);
+            assert!(repaired.current_state().on_break.active());
+
+            // Nothing needed quarantining
+            assert!(list_quarantined(&quarantine_dir(dir.as_ref()))
+                .await
+                .unwrap()
+                .is_empty());
+
+            fs::remove_dir_all(&dir).await.unwrap();
+        });
+    }
+}
@@ -0,0 +1,226 @@
+use std::error::Error;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+// Files written before versioning was added don't have this field, and count as version 0
+const VERSION_FIELD: &str = "version";
+
+// Upgrades a file's JSON from one version to the next
+pub type Migration = fn(&mut Value) -> Result<(), Box<dyn Error>>;
+
+pub trait Versioned: Serialize + DeserializeOwned {
+    // `MIGRATIONS[n]` upgrades a file from version n to n + 1, so the current version is the
+    // number of migrations
+    const MIGRATIONS: &'static [Migration];
+
+    fn version() -> usize {
+        Self::MIGRATIONS.len()
+    }
+}
+
+impl<T: Versioned> Versioned for Box<T> {
+    const MIGRATIONS: &'static [Migration] = T::MIGRATIONS;
+}
+
+// Version 1 only added the version field itself
+pub fn unversioned(_json: &mut Value) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
+pub fn to_value<T: Versioned>(value: &T) -> Result<Value, Box<dyn Error>> {
+    let mut json = serde_json::to_value(value)?;
+
+    json.as_object_mut()
+        .ok_or("versioned data must serialize to a JSON object")?
+        .insert(VERSION_FIELD.to_owned(), T::version().into());
+
+    Ok(json)
+}
+
+pub fn to_vec<T: Versioned>(value: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(serde_json::to_vec(&to_value(value)?)?)
+}
+
+pub fn from_value<T: Versioned>(mut json: Value) -> Result<T, Box<dyn Error>> {
+    let version = match json
+        .as_object_mut()
+        .ok_or("versioned data must be a JSON object")?
+        .remove(VERSION_FIELD)
+    {
+        Some(version) => version.as_u64().ok_or("version must be a number")? as usize,
+        None => 0,
+    };
+
+    if version > T::version() {
+        return Err(format!(
+            "data is from version {}, but only up to version {} is supported",
+            version,
+            T::version()
+        )
+        .into());
+    }
+
+    for migration in &T::MIGRATIONS[version..] {
+        migration(&mut json)?;
+    }
+
+    Ok(serde_json::from_value(json)?)
+}
+
+// The version data was written at, if it's newer than this build can read
+pub fn newer_version<T: Versioned>(json: &Value) -> Option<usize> {
+    let version = json.get(VERSION_FIELD)?.as_u64()? as usize;
+
+    (version > T::version()).then_some(version)
+}
+
+pub fn from_slice<T: Versioned>(buf: &[u8]) -> Result<T, Box<dyn Error>> {
+    from_value(serde_json::from_slice(buf)?)
+}
+
+// For versioned data nested inside something else, with `#[serde(with = "schema::nested")]`
+pub mod nested {
+    use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+
+    use super::Versioned;
+
+    pub fn serialize<T: Versioned, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::to_value(value)
+            .map_err(ser::Error::custom)?
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Versioned, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        super::from_value(Value::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::hours;
+    use crate::{settings::Settings, tasks, timecard::EventLog};
+    use serde::Deserialize;
+
+    // Fixtures of each file format, v0 being files from before versioning was added
+    const EVENT_LOG_V0: &str = include_str!("../tests/fixtures/v0/event-log.json");
+    const EVENT_LOG_V1: &str = include_str!("../tests/fixtures/v1/event-log.json");
+    const SETTINGS_V0: &str = include_str!("../tests/fixtures/v0/settings.json");
+    const SETTINGS_V1: &str = include_str!("../tests/fixtures/v1/settings.json");
+    const SETTINGS_V2: &str = include_str!("../tests/fixtures/v2/settings.json");
+    const RECENTS_V0: &str = include_str!("../tests/fixtures/v0/recents.json");
+    const RECENTS_V1: &str = include_str!("../tests/fixtures/v1/recents.json");
+    const TASK_V0: &str = include_str!("../tests/fixtures/v0/task.json");
+    const TASK_V1: &str = include_str!("../tests/fixtures/v1/task.json");
+
+    // Loads a fixture and writes it back out at the current version
+    fn upgrade<T: Versioned>(fixture: &str) -> Value {
+        let loaded: T = from_slice(fixture.as_bytes()).unwrap();
+        let json = to_value(&loaded).unwrap();
+
+        assert_eq!(json[VERSION_FIELD], T::version());
+
+        json
+    }
+
+    // The current format should come back out exactly as it went in
+    fn assert_round_trips<T: Versioned>(fixture: &str) {
+        let json: Value = serde_json::from_str(fixture).unwrap();
+
+        assert_eq!(upgrade::<T>(fixture), json);
+    }
+
+    #[test]
+    fn current_fixtures_round_trip() {
+        assert_round_trips::<EventLog>(EVENT_LOG_V1);
+        assert_round_trips::<Settings>(SETTINGS_V2);
+        assert_round_trips::<tasks::Recents>(RECENTS_V1);
+        assert_round_trips::<tasks::Task>(TASK_V1);
+    }
+
+    #[test]
+    fn unversioned_fixtures_upgrade() {
+        let event_log = upgrade::<EventLog>(EVENT_LOG_V0);
+        assert_eq!(event_log["events"].as_array().unwrap().len(), 5);
+        assert_eq!(event_log["dayStart"], "00:00:00");
+
+        let settings = upgrade::<Settings>(SETTINGS_V0);
+        assert_eq!(settings["categories"], Value::Array(Vec::new()));
+        assert_eq!(settings["schedule"]["weekly"][0]["secs"], 8 * 3600);
+
+        upgrade::<tasks::Recents>(RECENTS_V0);
+        upgrade::<tasks::Task>(TASK_V0);
+    }
+
+    #[test]
+    fn settings_work_target_applies_to_every_day() {
+        let settings: Settings = from_slice(SETTINGS_V1.as_bytes()).unwrap();
+        let date = |day| chrono::NaiveDate::from_ymd_opt(2023, 11, day).unwrap();
+
+        // Past weekends keep the target they had, so the flex bank doesn't change
+        assert_eq!(settings.schedule.target_for(date(10)), hours(8));
+        assert_eq!(settings.schedule.target_for(date(11)), hours(8));
+        assert_eq!(settings.schedule.target_for(date(12)), hours(8));
+        assert!(settings.schedule.exceptions.is_empty());
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Renamed {
+        new_name: String,
+        added: u32,
+    }
+
+    impl Versioned for Renamed {
+        const MIGRATIONS: &'static [Migration] = &[unversioned, rename, add];
+    }
+
+    fn rename(json: &mut Value) -> Result<(), Box<dyn Error>> {
+        let map = json.as_object_mut().unwrap();
+        let old = map.remove("old_name").ok_or("missing old_name")?;
+        map.insert("new_name".to_owned(), old);
+
+        Ok(())
+    }
+
+    fn add(json: &mut Value) -> Result<(), Box<dyn Error>> {
+        json["added"] = 3.into();
+
+        Ok(())
+    }
+
+    #[test]
+    fn migrations_run_from_file_version() {
+        let renamed: Renamed = from_slice(br#"{"old_name":"a"}"#).unwrap();
+        assert_eq!((renamed.new_name.as_str(), renamed.added), ("a", 3));
+
+        let renamed: Renamed = from_slice(br#"{"version":2,"new_name":"b"}"#).unwrap();
+        assert_eq!((renamed.new_name.as_str(), renamed.added), ("b", 3));
+
+        assert_eq!(
+            to_value(&renamed).unwrap(),
+            serde_json::json!({"version": 3, "new_name": "b", "added": 3})
+        );
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        assert!(from_slice::<Renamed>(br#"{"version":4,"new_name":"c","added":1}"#).is_err());
+
+        assert_eq!(
+            newer_version::<Renamed>(&serde_json::json!({"version": 4})),
+            Some(4)
+        );
+        assert_eq!(
+            newer_version::<Renamed>(&serde_json::json!({"version": 3})),
+            None
+        );
+        assert_eq!(newer_version::<Renamed>(&serde_json::json!({})), None);
+    }
+}
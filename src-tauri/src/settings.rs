@@ -1,12 +1,15 @@
-use std::error::Error;
+use std::{collections::BTreeMap, error::Error};
 
-use async_std::{
-    fs::File,
-    io::{ReadExt, WriteExt},
-    path::PathBuf,
+use crate::{
+    clock::Clock,
+    schema::{self, Versioned},
+    storage,
+    timecard::{Category, ClockType, DayStatus},
 };
-use chrono::prelude::*;
+use async_std::{fs::File, io::ReadExt, path::PathBuf};
+use chrono::{Datelike, NaiveDate};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Serialize, Deserialize)]
 pub struct Settings {
@@ -14,30 +17,204 @@ pub struct Settings {
     filename: PathBuf,
 
     pub current_date: chrono::NaiveDate,
-    pub work_target: std::time::Duration,
+    // Time of day that workdays roll over, so late nights count towards the previous day
+    #[serde(default)]
+    pub day_start: chrono::NaiveTime,
+    pub schedule: Schedule,
     pub lunch_target: std::time::Duration,
     pub break_target: std::time::Duration,
+    // How long to be idle while working before getting clocked out at the start of the idle time
+    #[serde(default)]
+    pub auto_clock_out: Option<std::time::Duration>,
+    // Idle time while working that lasts at least this long gets counted as a break instead
+    #[serde(default)]
+    pub auto_break: Option<std::time::Duration>,
+    // User-defined clocks on top of the built-in day, break and lunch clocks
+    #[serde(default)]
+    pub categories: Vec<CategorySettings>,
+    // Day the flex time bank starts counting from, otherwise it starts from the first logged day
+    #[serde(default)]
+    pub flex_start: Option<chrono::NaiveDate>,
+    // Archived days older than this only keep their summaries, otherwise their events are kept
+    #[serde(default)]
+    pub raw_event_retention_days: Option<u32>,
+}
+
+impl Versioned for Settings {
+    const MIGRATIONS: &'static [schema::Migration] = &[schema::unversioned, weekly_schedule];
+}
+
+// Version 2 replaced the single work target with a weekly schedule. The old target applied to every
+// day, weekends included, so it still does until the schedule gets changed, or past days' targets
+// would shift under the flex bank.
+fn weekly_schedule(json: &mut Value) -> Result<(), Box<dyn Error>> {
+    let settings = json.as_object_mut().ok_or("settings must be an object")?;
+    let work_target = settings
+        .remove("work_target")
+        .ok_or("settings are missing work_target")?;
+
+    let weekly = vec![work_target; 7];
+
+    settings.insert(
+        "schedule".to_owned(),
+        serde_json::json!({ "weekly": weekly, "exceptions": {} }),
+    );
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    // Work target for each day of the week, starting from Monday
+    pub weekly: [std::time::Duration; 7],
+    // Days that don't follow the weekly schedule
+    #[serde(default)]
+    pub exceptions: BTreeMap<NaiveDate, ScheduleException>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleException {
+    pub kind: ExceptionKind,
+    // What's left of the work target that day, so partial days off can still expect some work
+    #[serde(default)]
+    pub target: std::time::Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExceptionKind {
+    Holiday,
+    Pto,
+    Sick,
+    Other,
+}
+
+impl Schedule {
+    // The same target every weekday, with weekends off
+    pub fn weekdays(target: std::time::Duration) -> Schedule {
+        let mut weekly = [target; 7];
+        weekly[5] = std::time::Duration::ZERO;
+        weekly[6] = std::time::Duration::ZERO;
+
+        Schedule {
+            weekly,
+            exceptions: BTreeMap::new(),
+        }
+    }
+
+    pub fn target_for(&self, date: NaiveDate) -> std::time::Duration {
+        match self.exceptions.get(&date) {
+            Some(exception) => exception.target,
+            None => self.weekly_target(date),
+        }
+    }
+
+    fn weekly_target(&self, date: NaiveDate) -> std::time::Duration {
+        self.weekly[date.weekday().num_days_from_monday() as usize]
+    }
+
+    // Days off are only recorded as exceptions, with any time left to work as their target
+    pub fn status_for(&self, date: NaiveDate) -> DayStatus {
+        let Some(exception) = self.exceptions.get(&date) else {
+            return DayStatus::Worked;
+        };
+
+        let status = match exception.kind {
+            ExceptionKind::Holiday => DayStatus::Holiday,
+            ExceptionKind::Pto => DayStatus::Pto,
+            ExceptionKind::Sick => DayStatus::Sick,
+            ExceptionKind::Other => return DayStatus::Worked,
+        };
+
+        if exception.target.is_zero() {
+            status
+        } else {
+            DayStatus::PartialPto {
+                credited: self.weekly_target(date).saturating_sub(exception.target),
+            }
+        }
+    }
+
+    // Time off for the day, which has already been taken out of its target
+    pub fn credited_for(&self, date: NaiveDate) -> std::time::Duration {
+        self.status_for(date).credited(self.weekly_target(date))
+    }
+
+    pub fn set_status(&mut self, date: NaiveDate, status: DayStatus) {
+        let (kind, target) = match status {
+            DayStatus::Worked => {
+                // Exceptions that aren't days off are left alone
+                if !self.status_for(date).is_worked() {
+                    self.exceptions.remove(&date);
+                }
+
+                return;
+            }
+            DayStatus::Pto => (ExceptionKind::Pto, std::time::Duration::ZERO),
+            DayStatus::Sick => (ExceptionKind::Sick, std::time::Duration::ZERO),
+            DayStatus::Holiday => (ExceptionKind::Holiday, std::time::Duration::ZERO),
+            DayStatus::PartialPto { credited } => (
+                ExceptionKind::Pto,
+                self.weekly_target(date).saturating_sub(credited),
+            ),
+        };
+
+        let note = self
+            .exceptions
+            .remove(&date)
+            .and_then(|exception| exception.note);
+
+        self.exceptions
+            .insert(date, ScheduleException { kind, target, note });
+    }
+
+    // Days without a target don't count towards workdays
+    pub fn is_workday(&self, date: NaiveDate) -> bool {
+        !self.target_for(date).is_zero()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CategorySettings {
+    #[serde(flatten)]
+    pub category: Category,
+    // Shows a notification once the category has been clocked into for longer than this
+    #[serde(default)]
+    pub target: Option<std::time::Duration>,
 }
 
 impl Settings {
-    pub async fn load_or_new(filename: PathBuf) -> Result<Settings, Box<dyn Error>> {
+    pub async fn load_or_new(
+        filename: PathBuf,
+        clock: &dyn Clock,
+    ) -> Result<Settings, Box<dyn Error>> {
         if filename.exists().await {
             let mut file = File::open(&filename).await?;
 
             let mut buf = Vec::new();
             file.read_to_end(&mut buf).await?;
 
-            let mut settings: Settings = serde_json::from_slice(&buf)?;
+            let mut settings: Settings = schema::from_slice(&buf)?;
             settings.filename = filename;
             Ok(settings)
         } else {
             let settings = Settings {
                 filename,
 
-                current_date: Local::now().date_naive(),
-                work_target: std::time::Duration::from_secs(8 * 60 * 60),
+                current_date: clock.now().date_naive(),
+                day_start: chrono::NaiveTime::MIN,
+                schedule: Schedule::weekdays(std::time::Duration::from_secs(8 * 60 * 60)),
                 lunch_target: std::time::Duration::from_secs(60 * 60),
                 break_target: std::time::Duration::from_secs(30 * 60),
+                auto_clock_out: None,
+                auto_break: None,
+                categories: Vec::new(),
+                flex_start: None,
+                raw_event_retention_days: None,
             };
 
             settings.save().await?;
@@ -46,12 +223,116 @@ impl Settings {
         }
     }
 
+    // Fills in a category clock's rules from its definition, so they always match the settings
+    pub fn resolve_clock(&self, clock: ClockType) -> Result<ClockType, Box<dyn Error>> {
+        match clock {
+            ClockType::Category(category) => {
+                let settings = self
+                    .categories
+                    .iter()
+                    .find(|settings| settings.category.name == category.name)
+                    .ok_or_else(|| format!("unknown clock category \"{}\"", category.name))?;
+
+                Ok(ClockType::Category(settings.category.clone()))
+            }
+            clock => Ok(clock),
+        }
+    }
+
     pub async fn save(&self) -> Result<(), Box<dyn Error>> {
-        let json = serde_json::to_vec(self)?;
+        let json = schema::to_vec(self)?;
+
+        storage::write_atomic(&self.filename, &json).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::hours;
+
+    #[test]
+    fn schedule_targets_follow_weekdays_and_exceptions() {
+        let mut schedule = Schedule::weekdays(hours(8));
+        schedule.weekly[4] = hours(6);
+
+        let date = |day| NaiveDate::from_ymd_opt(2023, 11, day).unwrap();
+
+        schedule.exceptions.insert(
+            date(23),
+            ScheduleException {
+                kind: ExceptionKind::Holiday,
+                target: std::time::Duration::ZERO,
+                note: Some("Thanksgiving".to_owned()),
+            },
+        );
+        schedule.exceptions.insert(
+            date(22),
+            ScheduleException {
+                kind: ExceptionKind::Pto,
+                target: hours(4),
+                note: None,
+            },
+        );
+
+        // Monday through Sunday of a normal week
+        let targets: Vec<_> = (13..=19)
+            .map(|day| schedule.target_for(date(day)))
+            .collect();
+        assert_eq!(
+            targets,
+            vec![
+                hours(8),
+                hours(8),
+                hours(8),
+                hours(8),
+                hours(6),
+                hours(0),
+                hours(0)
+            ]
+        );
+        assert!(!schedule.is_workday(date(18)));
+
+        assert_eq!(schedule.target_for(date(22)), hours(4));
+        assert_eq!(schedule.target_for(date(23)), hours(0));
+        assert!(!schedule.is_workday(date(23)));
+        assert_eq!(schedule.target_for(date(24)), hours(6));
+    }
+
+    #[test]
+    fn day_statuses_are_kept_as_exceptions() {
+        let mut schedule = Schedule::weekdays(hours(8));
+        let date = NaiveDate::from_ymd_opt(2023, 11, 15).unwrap();
+
+        schedule.set_status(date, DayStatus::PartialPto { credited: hours(3) });
+        assert_eq!(schedule.target_for(date), hours(5));
+        assert_eq!(schedule.credited_for(date), hours(3));
+        assert_eq!(
+            schedule.status_for(date),
+            DayStatus::PartialPto { credited: hours(3) }
+        );
+
+        schedule.set_status(date, DayStatus::Sick);
+        assert_eq!(schedule.exceptions[&date].kind, ExceptionKind::Sick);
+        assert_eq!(schedule.target_for(date), hours(0));
+        assert_eq!(schedule.credited_for(date), hours(8));
+
+        schedule.set_status(date, DayStatus::Worked);
+        assert!(schedule.exceptions.is_empty());
 
-        let mut file = File::create(&self.filename).await?;
-        file.write_all(&json).await?;
+        // Working a day with different hours isn't a day off
+        schedule.exceptions.insert(
+            date,
+            ScheduleException {
+                kind: ExceptionKind::Other,
+                target: hours(4),
+                note: None,
+            },
+        );
+        assert_eq!(schedule.status_for(date), DayStatus::Worked);
+        assert_eq!(schedule.credited_for(date), hours(0));
 
-        Ok(())
+        schedule.set_status(date, DayStatus::Worked);
+        assert_eq!(schedule.target_for(date), hours(4));
     }
 }
@@ -0,0 +1,57 @@
+use std::error::Error;
+
+use async_std::{
+    fs::{self, File},
+    io::WriteExt,
+    path::{Path, PathBuf},
+};
+
+// Writes to a temporary file next to the destination, syncs it, then renames it over the
+// destination so a crash mid-write never leaves a truncated file behind
+pub async fn write_atomic(filename: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let temp_filename = temp_filename_for(filename);
+
+    let mut file = File::create(&temp_filename).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    fs::rename(&temp_filename, filename).await?;
+
+    #[cfg(unix)]
+    if let Some(dir) = filename.parent() {
+        File::open(dir).await?.sync_all().await?;
+    }
+
+    Ok(())
+}
+
+fn temp_filename_for(filename: &Path) -> PathBuf {
+    let mut temp_filename = filename.as_os_str().to_owned();
+    temp_filename.push(".tmp");
+
+    temp_filename.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn write_atomic_replaces_contents() {
+        async_std::task::block_on(async {
+            let dir = PathBuf::from(temp_dir("storage"));
+
+            let filename = dir.join("test.json");
+
+            write_atomic(&filename, b"first").await.unwrap();
+            write_atomic(&filename, b"second").await.unwrap();
+
+            assert_eq!(fs::read(&filename).await.unwrap(), b"second");
+            assert!(!temp_filename_for(&filename).exists().await);
+
+            fs::remove_dir_all(&dir).await.unwrap();
+        });
+    }
+}
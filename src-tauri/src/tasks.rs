@@ -1,11 +1,10 @@
 use std::error::Error;
 
-use async_std::{
-    fs::File,
-    io::{ReadExt, WriteExt},
-    path::PathBuf,
-    sync::RwLock,
+use crate::{
+    schema::{self, Versioned},
+    storage,
 };
+use async_std::{fs::File, io::ReadExt, path::PathBuf, sync::RwLock};
 use serde::{Deserialize, Serialize};
 
 pub struct TaskManager {
@@ -22,6 +21,10 @@ pub struct Recents {
     other: Vec<TaskID>,
 }
 
+impl Versioned for Recents {
+    const MIGRATIONS: &'static [schema::Migration] = &[schema::unversioned];
+}
+
 impl TaskManager {
     pub async fn load_or_new(tasks_dir: PathBuf) -> Result<TaskManager, Box<dyn Error>> {
         let recents_filename = tasks_dir.join("recents.json");
@@ -32,7 +35,7 @@ impl TaskManager {
             let mut buf = Vec::new();
             file.read_to_end(&mut buf).await?;
 
-            serde_json::from_slice(&buf)?
+            schema::from_slice(&buf)?
         } else {
             Recents {
                 starred: Vec::new(),
@@ -61,12 +64,9 @@ impl TaskManager {
     }
 
     async fn save_recents(&self, recents: &Recents) -> Result<(), Box<dyn Error>> {
-        let json = serde_json::to_vec(recents)?;
-
-        let mut file = File::create(self.tasks_dir.join("recents.json")).await?;
-        file.write_all(&json).await?;
+        let json = schema::to_vec(recents)?;
 
-        Ok(())
+        storage::write_atomic(&self.tasks_dir.join("recents.json"), &json).await
     }
 
     pub async fn make_recent(&self, id: TaskID, starred: bool) -> Result<(), Box<dyn Error>> {
@@ -127,22 +127,19 @@ impl TaskManager {
         let id = *next_id;
         next_id.0 += 1;
 
-        let mut next_id_file = File::create(self.tasks_dir.join("next-id")).await?;
         let json = serde_json::to_vec(&*next_id)?;
 
-        next_id_file.write_all(&json).await?;
+        storage::write_atomic(&self.tasks_dir.join("next-id"), &json).await?;
 
         Ok(id)
     }
 
     pub async fn save_task(&self, task: &Task) -> Result<(), Box<dyn Error>> {
-        let json = serde_json::to_vec(task)?;
+        let json = schema::to_vec(task)?;
 
         let filename = self.tasks_dir.join(format!("{}.json", task.id.0));
-        let mut file = File::create(&filename).await?;
-        file.write_all(&json).await?;
 
-        Ok(())
+        storage::write_atomic(&filename, &json).await
     }
 
     pub async fn load_task(&self, id: TaskID) -> Result<Task, Box<dyn Error>> {
@@ -152,14 +149,37 @@ impl TaskManager {
         let mut buf = Vec::new();
         file.read_to_end(&mut buf).await?;
 
-        let task = serde_json::from_slice(&buf)?;
+        let task = schema::from_slice(&buf)?;
         Ok(task)
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash, Default)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash, Default,
+)]
+#[serde(try_from = "RawTaskID")]
 pub struct TaskID(u32);
 
+// IDs used as map keys inside internally tagged enums (like task weights on events) get buffered
+// as strings before they're deserialized, so accept both
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTaskID {
+    Number(u32),
+    String(String),
+}
+
+impl TryFrom<RawTaskID> for TaskID {
+    type Error = std::num::ParseIntError;
+
+    fn try_from(raw: RawTaskID) -> Result<Self, Self::Error> {
+        match raw {
+            RawTaskID::Number(id) => Ok(TaskID(id)),
+            RawTaskID::String(id) => Ok(TaskID(id.parse()?)),
+        }
+    }
+}
+
 pub const TASK_ID_NONE: TaskID = TaskID(0);
 
 #[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -181,3 +201,7 @@ pub struct Task {
     pub story_type: StoryType,
     pub starred: bool,
 }
+
+impl Versioned for Task {
+    const MIGRATIONS: &'static [schema::Migration] = &[schema::unversioned];
+}
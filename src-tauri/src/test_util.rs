@@ -0,0 +1,64 @@
+use std::{sync::Arc, time::Duration};
+
+use async_std::path::PathBuf;
+use chrono::prelude::*;
+
+use crate::{
+    clock::{Clock, FakeClock},
+    tasks::TaskID,
+    timecard::{self, EventLog, State},
+};
+
+// Tests take place in November 2023, on Wednesday the 15th unless they need other days
+pub fn local(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+    Local
+        .with_ymd_and_hms(2023, 11, day, hour, minute, 0)
+        .unwrap()
+}
+
+pub fn time(hour: u32, minute: u32) -> DateTime<FixedOffset> {
+    local(15, hour, minute).fixed_offset()
+}
+
+pub fn hours(hours: u64) -> Duration {
+    Duration::from_secs(hours * 60 * 60)
+}
+
+pub fn minutes(minutes: u64) -> Duration {
+    Duration::from_secs(minutes * 60)
+}
+
+pub fn task(id: u32) -> TaskID {
+    serde_json::from_value(id.into()).unwrap()
+}
+
+// An empty log for the clock's current day that never gets saved
+pub fn new_event_log(clock: &Arc<FakeClock>) -> EventLog {
+    EventLog::new(
+        PathBuf::new(),
+        clock.now().date_naive(),
+        NaiveTime::MIN,
+        State::default(),
+        clock.clone(),
+    )
+}
+
+// An empty log for the clock's current day, saved where the app would keep it in `logs_dir`
+pub fn event_log_in(logs_dir: impl AsRef<std::path::Path>, clock: &Arc<FakeClock>) -> EventLog {
+    let date = clock.now().date_naive();
+
+    EventLog::new(
+        timecard::log_file_for_date(logs_dir.as_ref(), date).into(),
+        date,
+        NaiveTime::MIN,
+        State::default(),
+        clock.clone(),
+    )
+}
+
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("work-warden-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
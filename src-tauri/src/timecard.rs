@@ -1,15 +1,20 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     error::Error,
+    sync::Arc,
 };
 
+use crate::clock::{self, Clock};
+use crate::journal::{self, Journal, JournalEntry};
+use crate::schema::{self, Versioned};
+use crate::storage;
 use crate::tasks::TaskID;
 use async_std::prelude::*;
 use async_std::{fs::File, path::PathBuf};
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Deserialize, Default)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub struct TrackedTime {
     since: Option<chrono::DateTime<FixedOffset>>,
     accumulated: std::time::Duration,
@@ -33,38 +38,84 @@ impl TrackedTime {
         self.since.is_some()
     }
 
-    pub fn elapsed_for_date(&self, date: NaiveDate) -> std::time::Duration {
+    pub fn add(&mut self, duration: std::time::Duration) {
+        self.accumulated += duration;
+    }
+
+    // Takes a span back out, either from the running segment or from accumulated time
+    pub fn deduct(&mut self, span: &IdleSpan) {
+        match &mut self.since {
+            Some(since) if *since <= span.start => *since += span.end - span.start,
+            _ => self.accumulated = self.accumulated.saturating_sub(span.duration()),
+        }
+    }
+
+    pub fn elapsed_for_date<Tz: TimeZone>(
+        &self,
+        date: NaiveDate,
+        day_start: NaiveTime,
+        now: DateTime<Tz>,
+    ) -> std::time::Duration {
         let mut elapsed = self.accumulated;
 
-        if let Some(mut since) = self.since {
-            let now = Local::now();
+        if let Some(since) = self.since {
+            elapsed += running_for_date(since, date, day_start, now);
+        }
 
-            let day_start = NaiveDateTime::new(date, NaiveTime::MIN)
-                .and_local_timezone(now.timezone())
-                .unwrap();
-            let day_end = NaiveDateTime::new(date + chrono::Days::new(1), NaiveTime::MIN)
-                .and_local_timezone(now.timezone())
-                .unwrap();
+        elapsed
+    }
+}
 
-            let end = std::cmp::min(now, day_end);
+// Time since `since` that falls within the workday for `date`, up until `now`
+fn running_for_date<Tz: TimeZone>(
+    mut since: DateTime<FixedOffset>,
+    date: NaiveDate,
+    day_start: NaiveTime,
+    now: DateTime<Tz>,
+) -> std::time::Duration {
+    let timezone = now.timezone();
 
-            if since < end {
-                if since < day_start {
-                    since = day_start.fixed_offset();
-                }
+    let start = resolve_local(&timezone, NaiveDateTime::new(date, day_start));
+    let day_end = resolve_local(
+        &timezone,
+        NaiveDateTime::new(date + chrono::Days::new(1), day_start),
+    );
 
-                elapsed += (end.fixed_offset() - since).to_std().unwrap();
-            }
-        }
+    let end = std::cmp::min(now, day_end).fixed_offset();
 
-        elapsed
+    if since < start {
+        since = start.fixed_offset();
     }
+
+    (end - since).to_std().unwrap_or(std::time::Duration::ZERO)
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+// Converts a local time to an instant even around DST transitions. Ambiguous times (when the
+// clocks go back) resolve to the earlier instant, and skipped times (when the clocks go forward)
+// get pushed forward by the length of the gap.
+fn resolve_local<Tz: TimeZone>(timezone: &Tz, time: NaiveDateTime) -> DateTime<Tz> {
+    match timezone.from_local_datetime(&time) {
+        chrono::LocalResult::Single(resolved) => resolved,
+        chrono::LocalResult::Ambiguous(earliest, _) => earliest,
+        chrono::LocalResult::None => {
+            // Reading the time with the offset from before the gap pushes it forward by the
+            // gap's length. A day back is always before the gap since transitions are never
+            // that close together.
+            let before = timezone
+                .offset_from_utc_datetime(&(time - chrono::Duration::days(1)))
+                .fix();
+            timezone.from_utc_datetime(&(time - before))
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TrackedMultiTime<T: Ord> {
     since: Option<chrono::DateTime<FixedOffset>>,
     ids: BTreeSet<T>,
+    // Relative share of time for each ID, time is split evenly when empty
+    #[serde(default = "BTreeMap::new", skip_serializing_if = "BTreeMap::is_empty")]
+    weights: BTreeMap<T, u32>,
     accumulated: BTreeMap<T, std::time::Duration>,
 }
 
@@ -73,35 +124,40 @@ impl<T: Ord> Default for TrackedMultiTime<T> {
         TrackedMultiTime {
             since: None,
             ids: BTreeSet::new(),
+            weights: BTreeMap::new(),
             accumulated: BTreeMap::new(),
         }
     }
 }
 
 impl<T: Ord + Copy> TrackedMultiTime<T> {
-    pub fn set_tracked(&mut self, time: chrono::DateTime<FixedOffset>, ids: BTreeSet<T>) {
+    pub fn set_tracked(
+        &mut self,
+        time: chrono::DateTime<FixedOffset>,
+        ids: BTreeSet<T>,
+        weights: BTreeMap<T, u32>,
+    ) {
         let paused = self.pause(time);
 
         self.ids = ids;
+        self.weights = weights;
 
         if paused {
             self.resume(time);
         }
     }
 
+    pub fn tracks(&self, id: &T) -> bool {
+        self.ids.contains(id)
+    }
+
     pub fn pause(&mut self, time: chrono::DateTime<FixedOffset>) -> bool {
         if let Some(start) = self.since.take() {
             // Accumulate time
-            if !self.ids.is_empty() {
-                let per_id_time = (time - start).to_std().unwrap_or(std::time::Duration::ZERO)
-                    / (self.ids.len() as u32);
-
-                for &past_id in &self.ids {
-                    self.accumulated
-                        .entry(past_id)
-                        .and_modify(|d| *d += per_id_time)
-                        .or_insert(per_id_time);
-                }
+            let elapsed = (time - start).to_std().unwrap_or(std::time::Duration::ZERO);
+
+            for (past_id, id_time) in self.split(elapsed) {
+                *self.accumulated.entry(past_id).or_default() += id_time;
             }
 
             true
@@ -110,6 +166,64 @@ impl<T: Ord + Copy> TrackedMultiTime<T> {
         }
     }
 
+    // Splits a span of time between the tracked IDs according to their weights
+    fn split(&self, elapsed: std::time::Duration) -> Vec<(T, std::time::Duration)> {
+        let weight = |id: &T| {
+            if self.weights.is_empty() {
+                1
+            } else {
+                self.weights.get(id).copied().unwrap_or(0)
+            }
+        };
+
+        let total_weight: u128 = self.ids.iter().map(|id| weight(id) as u128).sum();
+
+        if total_weight == 0 {
+            return Vec::new();
+        }
+
+        self.ids
+            .iter()
+            .map(|&id| {
+                let nanos = elapsed.as_nanos() * weight(&id) as u128 / total_weight;
+
+                (id, std::time::Duration::from_nanos(nanos as u64))
+            })
+            .collect()
+    }
+
+    // Accumulated time per ID plus each ID's share of the running segment within `date`
+    pub fn elapsed_for_date<Tz: TimeZone>(
+        &self,
+        date: NaiveDate,
+        day_start: NaiveTime,
+        now: DateTime<Tz>,
+    ) -> BTreeMap<T, std::time::Duration> {
+        let mut elapsed = self.accumulated.clone();
+
+        if let Some(since) = self.since {
+            for (id, id_time) in self.split(running_for_date(since, date, day_start, now)) {
+                *elapsed.entry(id).or_default() += id_time;
+            }
+        }
+
+        elapsed
+    }
+
+    // Same as `TrackedTime::deduct`, with accumulated time taken out of each ID by its share
+    pub fn deduct(&mut self, span: &IdleSpan) {
+        match &mut self.since {
+            Some(since) if *since <= span.start => *since += span.end - span.start,
+            _ => {
+                for (id, id_time) in self.split(span.duration()) {
+                    if let Some(accumulated) = self.accumulated.get_mut(&id) {
+                        *accumulated = accumulated.saturating_sub(id_time);
+                    }
+                }
+            }
+        }
+    }
+
     pub fn resume(&mut self, time: chrono::DateTime<FixedOffset>) -> bool {
         if self.since.is_none() {
             self.since = Some(time);
@@ -121,17 +235,35 @@ impl<T: Ord + Copy> TrackedMultiTime<T> {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Default)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct State {
-    working: TrackedTime,
-    on_break: TrackedTime,
-    on_lunch: TrackedTime,
+    pub working: TrackedTime,
+    pub on_break: TrackedTime,
+    pub on_lunch: TrackedTime,
+
+    pub idle_work: TrackedTime,
+    pub active_until: Option<chrono::DateTime<FixedOffset>>,
+
+    pub tasks: TrackedMultiTime<TaskID>,
+
+    // Idle time while working that hasn't been resolved as work, break, lunch or dropped yet
+    #[serde(default)]
+    pub pending_idle: Vec<IdleSpan>,
 
-    idle_work: TrackedTime,
-    active_until: Option<chrono::DateTime<FixedOffset>>,
+    // Time for user-defined clocks, by category name
+    #[serde(default)]
+    pub categories: BTreeMap<String, CategoryTime>,
+}
 
-    tasks: TrackedMultiTime<TaskID>,
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryTime {
+    // Rules from the last time the category was clocked into
+    pub counts_as_work: bool,
+    pub pauses_tasks: bool,
+    #[serde(flatten)]
+    pub time: TrackedTime,
 }
 
 impl State {
@@ -141,322 +273,2331 @@ impl State {
         self.on_lunch.accumulated = std::time::Duration::ZERO;
         self.idle_work.accumulated = std::time::Duration::ZERO;
         self.tasks.accumulated = BTreeMap::new();
-    }
-}
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct EventLog {
-    initial_state: State,
-    current_state: State,
-    events: BTreeSet<Event>,
+        self.categories.retain(|_, category| category.time.active());
+        for category in self.categories.values_mut() {
+            category.time.accumulated = std::time::Duration::ZERO;
+        }
 
-    date: NaiveDate,
+        // Idle spans can only be resolved against the accumulations they happened in
+        self.pending_idle.clear();
+    }
 
-    #[serde(skip)]
-    filename: PathBuf,
-}
+    fn category_active(&self) -> bool {
+        self.categories
+            .values()
+            .any(|category| category.time.active())
+    }
 
-impl EventLog {
-    pub fn new(filename: PathBuf, date: NaiveDate, initial_state: State) -> EventLog {
-        EventLog {
-            current_state: initial_state.clone(),
-            initial_state,
-            events: BTreeSet::new(),
-            date,
-            filename,
+    // Only resumes tasks if nothing else that pauses them is still clocked in
+    fn resume_tasks(&mut self, time: DateTime<FixedOffset>) {
+        let paused = self.on_break.active()
+            || self.on_lunch.active()
+            || self
+                .categories
+                .values()
+                .any(|category| category.pauses_tasks && category.time.active());
+
+        if !paused {
+            self.tasks.resume(time);
         }
     }
 
-    pub fn add_event(&mut self, event: Event) {
-        match &event {
-            Event::ClockIn { clock, time } => match clock {
+    pub fn apply(&mut self, event: &Event) {
+        match event {
+            Event::ClockIn { clock, time, .. } => match clock {
                 ClockType::Day => {
-                    self.current_state.working.start_at(*time);
-                    self.current_state.tasks.resume(*time);
+                    self.working.start_at(*time);
+                    self.tasks.resume(*time);
                 }
                 ClockType::Break => {
-                    self.current_state.on_break.start_at(*time);
-                    self.current_state.working.start_at(*time);
-                    self.current_state.on_lunch.end_at(*time);
-                    self.current_state.idle_work.end_at(*time);
-                    self.current_state.tasks.pause(*time);
+                    self.on_break.start_at(*time);
+                    self.working.start_at(*time);
+                    self.on_lunch.end_at(*time);
+                    self.idle_work.end_at(*time);
+                    self.tasks.pause(*time);
                 }
                 ClockType::Lunch => {
-                    self.current_state.on_lunch.start_at(*time);
-                    self.current_state.working.start_at(*time);
-                    self.current_state.on_break.end_at(*time);
-                    self.current_state.idle_work.end_at(*time);
-                    self.current_state.tasks.pause(*time);
+                    self.on_lunch.start_at(*time);
+                    self.working.start_at(*time);
+                    self.on_break.end_at(*time);
+                    self.idle_work.end_at(*time);
+                    self.tasks.pause(*time);
+                }
+                ClockType::Category(category) => {
+                    let entry = self
+                        .categories
+                        .entry(category.name.clone())
+                        .or_insert_with(|| CategoryTime {
+                            counts_as_work: category.counts_as_work,
+                            pauses_tasks: category.pauses_tasks,
+                            time: TrackedTime::default(),
+                        });
+
+                    if !entry.time.active() {
+                        entry.counts_as_work = category.counts_as_work;
+                        entry.pauses_tasks = category.pauses_tasks;
+                    }
+
+                    entry.time.start_at(*time);
+                    self.working.start_at(*time);
+                    self.idle_work.end_at(*time);
+
+                    if category.pauses_tasks {
+                        self.tasks.pause(*time);
+                    }
                 }
             },
-            Event::ClockOut { clock, time } => match clock {
+            Event::ClockOut { clock, time, .. } => match clock {
                 ClockType::Day => {
-                    self.current_state.working.end_at(*time);
-                    self.current_state.on_break.end_at(*time);
-                    self.current_state.on_lunch.end_at(*time);
-                    self.current_state.idle_work.end_at(*time);
-                    self.current_state.tasks.pause(*time);
+                    self.working.end_at(*time);
+                    self.on_break.end_at(*time);
+                    self.on_lunch.end_at(*time);
+                    self.idle_work.end_at(*time);
+                    self.tasks.pause(*time);
+
+                    for category in self.categories.values_mut() {
+                        category.time.end_at(*time);
+                    }
                 }
                 ClockType::Break => {
-                    self.current_state.on_break.end_at(*time);
-                    self.current_state.tasks.resume(*time);
+                    self.on_break.end_at(*time);
+                    self.resume_tasks(*time);
                 }
                 ClockType::Lunch => {
-                    self.current_state.on_lunch.end_at(*time);
-                    self.current_state.tasks.resume(*time);
+                    self.on_lunch.end_at(*time);
+                    self.resume_tasks(*time);
+                }
+                ClockType::Category(category) => {
+                    if let Some(entry) = self.categories.get_mut(&category.name) {
+                        entry.time.end_at(*time);
+                        self.resume_tasks(*time);
+                    }
                 }
             },
-            Event::Active { time } => {
-                self.current_state.active_until = Some(*time);
-                self.current_state.idle_work.end_at(*time);
+            Event::Active { time, .. } => {
+                self.active_until = Some(*time);
+
+                if let Some(start) = self.idle_work.since {
+                    self.pending_idle.push(IdleSpan { start, end: *time });
+                }
+
+                self.idle_work.end_at(*time);
             }
-            Event::Idle { time } => {
-                self.current_state.active_until = None;
+            Event::Idle { time, .. } => {
+                self.active_until = None;
 
-                if self.current_state.working.active()
-                    && !self.current_state.on_break.active()
-                    && !self.current_state.on_lunch.active()
+                if self.working.active()
+                    && !self.on_break.active()
+                    && !self.on_lunch.active()
+                    && !self.category_active()
                 {
-                    self.current_state.idle_work.start_at(*time);
+                    self.idle_work.start_at(*time);
+                }
+            }
+            Event::Tasks {
+                tasks,
+                weights,
+                time,
+                ..
+            } => self
+                .tasks
+                .set_tracked(*time, tasks.clone(), weights.clone()),
+            Event::ResolveIdle {
+                span, resolution, ..
+            } => {
+                // Spans that aren't pending were already resolved, or never were idle
+                let pending = self.pending_idle.len();
+                self.pending_idle.retain(|pending| pending != span);
+
+                if self.pending_idle.len() == pending {
+                    return;
+                }
+
+                self.idle_work.deduct(span);
+
+                match resolution {
+                    IdleResolution::Work => {}
+                    IdleResolution::Break => {
+                        self.on_break.add(span.duration());
+                        self.tasks.deduct(span);
+                    }
+                    IdleResolution::Lunch => {
+                        self.on_lunch.add(span.duration());
+                        self.tasks.deduct(span);
+                    }
+                    IdleResolution::Drop => {
+                        self.working.deduct(span);
+                        self.tasks.deduct(span);
+                    }
                 }
             }
-            Event::Tasks { tasks, time } => {
-                self.current_state.tasks.set_tracked(*time, tasks.clone())
+            Event::ManualTime {
+                task,
+                duration,
+                counts_as_work,
+                ..
+            } => {
+                *self.tasks.accumulated.entry(*task).or_default() += *duration;
+
+                if *counts_as_work {
+                    self.working.add(*duration);
+                }
             }
         }
-
-        self.events.insert(event);
-    }
-
-    pub async fn save(&self) -> Result<(), Box<dyn Error>> {
-        let json = serde_json::to_vec(self)?;
-
-        let mut file = File::create(&self.filename).await?;
-        file.write_all(&json).await?;
-
-        Ok(())
     }
+}
 
-    pub async fn load(filename: PathBuf) -> Result<EventLog, Box<dyn Error>> {
-        let mut file = File::open(&filename).await?;
-
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf).await?;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct IdleSpan {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
 
-        let mut event_log: EventLog = serde_json::from_slice(&buf)?;
-        event_log.filename = filename;
-        Ok(event_log)
+impl IdleSpan {
+    pub fn duration(&self) -> std::time::Duration {
+        (self.end - self.start)
+            .to_std()
+            .unwrap_or(std::time::Duration::ZERO)
     }
 
-    pub fn get_state(&self) -> State {
-        self.current_state.clone()
+    // Spans coming back from the frontend lose sub-millisecond precision
+    pub fn same_as(&self, other: &IdleSpan) -> bool {
+        self.start.timestamp_millis() == other.start.timestamp_millis()
+            && self.end.timestamp_millis() == other.end.timestamp_millis()
     }
+}
 
-    pub fn infer_idle(&mut self) -> bool {
-        let now = Local::now().fixed_offset();
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum IdleResolution {
+    // Keep the idle time as working time
+    Work,
+    Break,
+    Lunch,
+    // Take the idle time out of working time entirely
+    Drop,
+}
 
-        if let Some(active_until) = &self.current_state.active_until {
-            // App was closed or something, add an idle event from last known active time
-            if now - active_until > chrono::Duration::minutes(5) {
-                println!("was last active >5m ago, injecting idle event");
-                self.add_event(Event::Idle {
-                    time: *active_until,
-                });
-                self.current_state.active_until = None;
+// Whether a day was worked or taken off, which credits time towards the work target
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DayStatus {
+    #[default]
+    Worked,
+    Pto,
+    Sick,
+    Holiday,
+    PartialPto {
+        credited: std::time::Duration,
+    },
+}
 
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        }
+impl DayStatus {
+    pub fn is_worked(&self) -> bool {
+        *self == DayStatus::Worked
     }
 
-    pub fn force_active(&mut self) {
-        let now = Local::now().fixed_offset();
-
-        if self.current_state.active_until.is_none() {
-            self.add_event(Event::Active { time: now });
+    // Full days off are credited with the whole work target for the day
+    pub fn credited(&self, work_target: std::time::Duration) -> std::time::Duration {
+        match *self {
+            DayStatus::Worked => std::time::Duration::ZERO,
+            DayStatus::Pto | DayStatus::Sick | DayStatus::Holiday => work_target,
+            DayStatus::PartialPto { credited } => credited,
         }
-
-        self.current_state.active_until = Some(now);
     }
+}
 
-    pub async fn refresh_active(&mut self) -> Result<(), Box<dyn Error>> {
-        if self.current_state.active_until.is_some() {
-            self.current_state.active_until = Some(Local::now().fixed_offset());
-            self.save().await?;
-        }
-
-        Ok(())
-    }
+pub fn log_file_for_date(logs_dir: &std::path::Path, date: NaiveDate) -> std::path::PathBuf {
+    logs_dir.join(format!(
+        "{}-{}-{}.log.json",
+        date.year(),
+        date.month(),
+        date.day()
+    ))
+}
 
-    pub fn elapsed(&self) -> ElapsedSummary {
-        ElapsedSummary {
-            work_time: self.current_state.working.elapsed_for_date(self.date),
-            break_time: self.current_state.on_break.elapsed_for_date(self.date),
-            lunch_time: self.current_state.on_lunch.elapsed_for_date(self.date),
-            idle_work_time: self.current_state.idle_work.elapsed_for_date(self.date),
+pub fn date_for_log_file(log_file: &std::path::Path) -> Option<NaiveDate> {
+    let name = log_file.file_name()?.to_str()?;
+    let date = name.strip_suffix(".log.json")?;
 
-            working: self.current_state.working.active(),
-            on_break: self.current_state.on_break.active(),
-            on_lunch: self.current_state.on_lunch.active(),
-            idle_work: self.current_state.idle_work.active(),
-        }
-    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
 }
 
-pub struct ElapsedSummary {
-    pub work_time: std::time::Duration,
-    pub break_time: std::time::Duration,
-    pub lunch_time: std::time::Duration,
-    pub idle_work_time: std::time::Duration,
+// The workday a point in time belongs to, where workdays start at `day_start` rather than midnight
+pub fn workday_for<Tz: TimeZone>(time: &DateTime<Tz>, day_start: NaiveTime) -> NaiveDate {
+    (time.naive_local() - (day_start - NaiveTime::MIN)).date()
+}
 
-    pub working: bool,
-    pub on_break: bool,
-    pub on_lunch: bool,
-    pub idle_work: bool,
+// Blank notes are the same as no note
+fn clean_note(note: Option<String>) -> Option<String> {
+    note.map(|note| note.trim().to_owned())
+        .filter(|note| !note.is_empty())
 }
 
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum Event {
-    ClockIn {
-        time: DateTime<FixedOffset>,
-        clock: ClockType,
-    },
-    ClockOut {
-        time: DateTime<FixedOffset>,
-        clock: ClockType,
-    },
-    Active {
-        time: DateTime<FixedOffset>,
-    },
-    Idle {
-        time: DateTime<FixedOffset>,
-    },
-    Tasks {
-        time: DateTime<FixedOffset>,
-        tasks: BTreeSet<TaskID>,
-    },
+pub fn replay<'a>(initial_state: &State, events: impl IntoIterator<Item = &'a Event>) -> State {
+    let mut state = initial_state.clone();
+
+    for event in events {
+        state.apply(event);
+    }
+
+    state
 }
 
-impl std::cmp::Ord for Event {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let self_time = self.time();
-        let other_time = other.time();
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventLog {
+    initial_state: State,
+    current_state: State,
+    events: BTreeSet<Event>,
 
-        let time_cmp = self_time.cmp(&other_time);
-        if time_cmp != std::cmp::Ordering::Equal {
-            return time_cmp;
-        }
+    date: NaiveDate,
+    #[serde(default)]
+    day_start: NaiveTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(default, skip_serializing_if = "DayStatus::is_worked")]
+    status: DayStatus,
+    // Focus intervals finished for each task
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pomodoros: BTreeMap<TaskID, u32>,
 
-        let self_variant = self.variant_id();
-        let other_variant = other.variant_id();
+    #[serde(skip)]
+    filename: PathBuf,
+    #[serde(skip, default = "clock::system")]
+    clock: Arc<dyn Clock>,
+    #[serde(skip)]
+    journal: Journal,
+}
 
-        let variant_cmp = self_variant.cmp(&other_variant);
-        if variant_cmp != std::cmp::Ordering::Equal {
-            return variant_cmp;
+impl EventLog {
+    pub fn new(
+        filename: PathBuf,
+        date: NaiveDate,
+        day_start: NaiveTime,
+        initial_state: State,
+        clock: Arc<dyn Clock>,
+    ) -> EventLog {
+        EventLog {
+            current_state: initial_state.clone(),
+            initial_state,
+            events: BTreeSet::new(),
+            date,
+            day_start,
+            note: None,
+            status: DayStatus::Worked,
+            pomodoros: BTreeMap::new(),
+            filename,
+            clock,
+            journal: Journal::default(),
         }
+    }
 
-        match (self, other) {
-            (
-                Event::ClockIn {
-                    clock: self_clock, ..
-                },
-                Event::ClockIn {
-                    clock: other_clock, ..
-                },
-            ) => self_clock.cmp(other_clock),
-            (
-                Event::ClockOut {
-                    clock: self_clock, ..
-                },
-                Event::ClockOut {
-                    clock: other_clock, ..
-                },
-            ) => self_clock.cmp(other_clock),
-            (Event::Idle { .. }, Event::Idle { .. }) => std::cmp::Ordering::Equal,
-            (Event::Active { .. }, Event::Active { .. }) => std::cmp::Ordering::Equal,
-            (
-                Event::Tasks {
-                    tasks: self_tasks, ..
-                },
-                Event::Tasks {
-                    tasks: other_tasks, ..
-                },
-            ) => self_tasks.cmp(other_tasks),
-            _ => unreachable!(),
+    pub async fn load_or_new(
+        filename: PathBuf,
+        date: NaiveDate,
+        day_start: NaiveTime,
+        clock: Arc<dyn Clock>,
+    ) -> Result<EventLog, Box<dyn Error>> {
+        if EventLog::exists(filename.clone()).await {
+            EventLog::load(filename, clock).await
+        } else {
+            Ok(EventLog::new(
+                filename,
+                date,
+                day_start,
+                State::default(),
+                clock,
+            ))
         }
     }
-}
 
-impl std::cmp::PartialOrd for Event {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    // Rebuilds an event log from pieces salvaged out of a damaged one
+    pub fn recovered(
+        filename: PathBuf,
+        date: NaiveDate,
+        day_start: NaiveTime,
+        initial_state: State,
+        events: impl IntoIterator<Item = Event>,
+        clock: Arc<dyn Clock>,
+    ) -> EventLog {
+        let mut event_log = EventLog::new(filename, date, day_start, initial_state, clock);
+
+        event_log.events.extend(events);
+        event_log.replay();
+
+        event_log
     }
-}
 
-impl Event {
-    pub fn clock_in(clock: ClockType) -> Event {
-        Event::ClockIn {
-            time: Local::now().fixed_offset(),
-            clock,
-        }
+    // Points a log read from somewhere other than its own file, like an archive, back at its file
+    pub fn relocate(&mut self, filename: PathBuf, clock: Arc<dyn Clock>) {
+        self.filename = filename;
+        self.clock = clock;
     }
 
-    pub fn clock_out(clock: ClockType) -> Event {
-        Event::ClockOut {
-            time: Local::now().fixed_offset(),
-            clock,
-        }
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
     }
 
-    pub fn active() -> Event {
-        Event::Active {
-            time: Local::now().fixed_offset(),
-        }
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        self.clock.now().fixed_offset()
     }
 
-    pub fn idle() -> Event {
-        Event::Idle {
-            time: Local::now().fixed_offset(),
-        }
+    pub fn add_event(&mut self, event: Event) {
+        self.journal.push(JournalEntry::Event(event.clone()));
+        self.current_state.apply(&event);
+        self.events.insert(event);
     }
 
-    pub fn tasks(tasks: BTreeSet<TaskID>) -> Event {
-        Event::Tasks {
-            time: Local::now().fixed_offset(),
-            tasks,
-        }
+    fn set_active_until(&mut self, time: Option<DateTime<FixedOffset>>) {
+        self.journal.push(JournalEntry::ActiveUntil { time });
+        self.current_state.active_until = time;
     }
 
-    pub fn time(&self) -> DateTime<FixedOffset> {
-        match *self {
-            Event::ClockIn { time, .. } => time,
-            Event::ClockOut { time, .. } => time,
-            Event::Active { time } => time,
-            Event::Idle { time } => time,
-            Event::Tasks { time, .. } => time,
+    pub fn insert_event(&mut self, event: Event) -> Result<(), Box<dyn Error>> {
+        event.validate()?;
+        self.check_event_time(&event)?;
+
+        if self.find_event(&event).is_some() {
+            return Err("event already exists".into());
         }
-    }
 
-    fn variant_id(&self) -> u32 {
+        self.events.insert(event);
+        self.replay();
+
+        Ok(())
+    }
+
+    pub fn remove_event(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let existing = self.find_event(event).ok_or("event not found")?;
+
+        self.events.remove(&existing);
+        self.replay();
+
+        Ok(())
+    }
+
+    pub fn amend_event(&mut self, old: &Event, new: Event) -> Result<(), Box<dyn Error>> {
+        new.validate()?;
+        self.check_event_time(&new)?;
+
+        let existing = self.find_event(old).ok_or("event not found")?;
+        self.events.remove(&existing);
+
+        if self.find_event(&new).is_some() {
+            self.events.insert(existing);
+            return Err("event already exists".into());
+        }
+
+        self.events.insert(new);
+        self.replay();
+
+        Ok(())
+    }
+
+    pub fn replay(&mut self) {
+        let mut state = replay(&self.initial_state, &self.events);
+
+        // Keep the latest refreshed active time, since refreshes aren't recorded as events
+        if state.active_until.is_some() {
+            state.active_until = std::cmp::max(state.active_until, self.current_state.active_until);
+        }
+
+        self.current_state = state;
+        self.journal.request_snapshot();
+    }
+
+    // Drops activity events that didn't change anything besides when we were last active, keeping
+    // the log's state the same when it's replayed
+    pub fn prune_activity(&mut self) {
+        let mut state = self.initial_state.clone();
+
+        self.events.retain(|event| {
+            let mut next = state.clone();
+            next.apply(event);
+
+            let activity = matches!(
+                event,
+                Event::Active { note: None, .. } | Event::Idle { note: None, .. }
+            );
+            let unchanged = State {
+                active_until: state.active_until,
+                ..next.clone()
+            } == state
+                && next.active_until.is_some() == state.active_until.is_some();
+
+            if activity && unchanged {
+                return false;
+            }
+
+            state = next;
+            true
+        });
+
+        self.journal.request_snapshot();
+    }
+
+    pub fn set_event_note(
+        &mut self,
+        event: &Event,
+        note: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let existing = self.find_event(event).ok_or("event not found")?;
+
+        self.events.remove(&existing);
+        self.events.insert(existing.with_note(clean_note(note)));
+        self.journal.request_snapshot();
+
+        Ok(())
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    pub fn set_note(&mut self, note: Option<String>) {
+        self.note = clean_note(note);
+        self.journal.request_snapshot();
+    }
+
+    pub fn status(&self) -> DayStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: DayStatus) {
+        self.status = status;
+        self.journal.request_snapshot();
+    }
+
+    pub fn pomodoros(&self) -> &BTreeMap<TaskID, u32> {
+        &self.pomodoros
+    }
+
+    pub fn complete_pomodoro(&mut self, task: TaskID) {
+        *self.pomodoros.entry(task).or_default() += 1;
+        self.journal.request_snapshot();
+    }
+
+    pub fn day_start(&self) -> NaiveTime {
+        self.day_start
+    }
+
+    fn find_event(&self, event: &Event) -> Option<Event> {
+        self.events
+            .iter()
+            .find(|existing| existing.same_as(event))
+            .cloned()
+    }
+
+    fn check_event_time(&self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let time = event.time();
+
+        if !self.contains(time) {
+            return Err(format!("event time {} is not on {}", time, self.date).into());
+        }
+
+        if time > self.now() {
+            return Err(format!("event time {} is in the future", time).into());
+        }
+
+        Ok(())
+    }
+
+    // Appends any changes since the last save to the journal
+    pub async fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut journal = std::mem::take(&mut self.journal);
+        let result = journal
+            .flush(&journal::filename_for(&self.filename), self)
+            .await;
+        self.journal = journal;
+
+        result
+    }
+
+    // Writes the whole event log out and drops its journal, used once the day is over
+    pub async fn compact(&mut self) -> Result<(), Box<dyn Error>> {
+        let json = schema::to_vec(self)?;
+
+        storage::write_atomic(&self.filename, &json).await?;
+        journal::remove(&journal::filename_for(&self.filename)).await?;
+
+        self.journal = Journal::default();
+
+        Ok(())
+    }
+
+    pub async fn exists(filename: PathBuf) -> bool {
+        filename.exists().await || journal::filename_for(&filename).exists().await
+    }
+
+    pub async fn load(
+        filename: PathBuf,
+        clock: Arc<dyn Clock>,
+    ) -> Result<EventLog, Box<dyn Error>> {
+        let journal_filename = journal::filename_for(&filename);
+
+        let mut event_log = if journal_filename.exists().await {
+            let (entries, truncated) = journal::read(&journal_filename).await?;
+            EventLog::from_journal(entries, truncated)?
+        } else {
+            let mut file = File::open(&filename).await?;
+
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf).await?;
+
+            schema::from_slice(&buf)?
+        };
+
+        event_log.filename = filename;
+        event_log.clock = clock;
+        Ok(event_log)
+    }
+
+    fn from_journal(
+        entries: Vec<JournalEntry>,
+        truncated: bool,
+    ) -> Result<EventLog, Box<dyn Error>> {
+        let mut entries = entries.into_iter();
+
+        let mut event_log = match entries.next() {
+            Some(JournalEntry::Snapshot(event_log)) => *event_log,
+            _ => return Err("journal doesn't start with a snapshot".into()),
+        };
+
+        let mut entries_since_snapshot = 0;
+
+        for entry in entries {
+            match entry {
+                JournalEntry::Snapshot(snapshot) => {
+                    event_log = *snapshot;
+                    entries_since_snapshot = 0;
+                    continue;
+                }
+                JournalEntry::Event(event) => {
+                    event_log.current_state.apply(&event);
+                    event_log.events.insert(event);
+                }
+                JournalEntry::ActiveUntil { time } => {
+                    event_log.current_state.active_until = time;
+                }
+            }
+
+            entries_since_snapshot += 1;
+        }
+
+        event_log.journal = Journal::loaded(entries_since_snapshot);
+
+        // Rewrite the journal before anything gets appended after a partial entry
+        if truncated {
+            event_log.journal.request_snapshot();
+        }
+
+        Ok(event_log)
+    }
+
+    pub fn get_state(&self) -> State {
+        self.current_state.clone()
+    }
+
+    pub fn initial_state(&self) -> &State {
+        &self.initial_state
+    }
+
+    pub fn current_state(&self) -> &State {
+        &self.current_state
+    }
+
+    pub fn events(&self) -> &BTreeSet<Event> {
+        &self.events
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    pub fn contains(&self, time: DateTime<FixedOffset>) -> bool {
+        workday_for(&time.with_timezone(&Local), self.day_start) == self.date
+    }
+
+    pub fn infer_idle(&mut self) -> bool {
+        let now = self.now();
+
+        if let Some(active_until) = &self.current_state.active_until {
+            // App was closed or something, add an idle event from last known active time
+            if now - active_until > chrono::Duration::minutes(5) {
+                println!("was last active >5m ago, injecting idle event");
+                self.add_event(Event::idle(*active_until));
+
+                true
+            } else {
+                false
+            }
+        } else {
+            false
+        }
+    }
+
+    pub fn resolve_idle(
+        &mut self,
+        span: IdleSpan,
+        resolution: IdleResolution,
+    ) -> Result<(), Box<dyn Error>> {
+        let span = *self
+            .current_state
+            .pending_idle
+            .iter()
+            .find(|pending| pending.same_as(&span))
+            .ok_or("idle span is not pending")?;
+
+        let now = self.now();
+        self.add_event(Event::resolve_idle(now, span, resolution));
+
+        Ok(())
+    }
+
+    // Resolves pending idle spans lasting at least `threshold` as breaks, returning whether there
+    // were any
+    pub fn auto_break(&mut self, threshold: std::time::Duration) -> bool {
+        let spans: Vec<IdleSpan> = self
+            .current_state
+            .pending_idle
+            .iter()
+            .filter(|span| span.duration() >= threshold)
+            .copied()
+            .collect();
+
+        let now = self.now();
+
+        for &span in &spans {
+            self.add_event(Event::resolve_idle(now, span, IdleResolution::Break));
+        }
+
+        !spans.is_empty()
+    }
+
+    // Clocks out retroactively once idle work has gone on for longer than `idle_limit`, returning
+    // the time it clocked out at
+    pub fn auto_clock_out(
+        &mut self,
+        idle_limit: std::time::Duration,
+    ) -> Option<DateTime<FixedOffset>> {
+        let idle_since = self.current_state.idle_work.since?;
+
+        let idle_time = (self.now() - idle_since)
+            .to_std()
+            .unwrap_or(std::time::Duration::ZERO);
+
+        if idle_time < idle_limit {
+            return None;
+        }
+
+        // Idle time carried over from the previous day gets clocked out at the start of this one
+        let time = std::cmp::max(idle_since, self.start_time());
+
+        self.events.insert(Event::clock_out(time, ClockType::Day));
+        self.replay();
+
+        Some(time)
+    }
+
+    // Logs time to a task without tracking it live. Without a time, entries for today are stamped
+    // with the current time and entries for past days with the start of that day.
+    pub fn add_manual_time(
+        &mut self,
+        time: Option<DateTime<FixedOffset>>,
+        task: TaskID,
+        duration: std::time::Duration,
+        counts_as_work: bool,
+        note: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let now = self.now();
+        let time = match time {
+            Some(time) => time,
+            None if self.contains(now) => now,
+            None => self.start_time(),
+        };
+
+        let event =
+            Event::manual_time(time, task, duration, counts_as_work)?.with_note(clean_note(note));
+
+        self.insert_event(event)
+    }
+
+    fn start_time(&self) -> DateTime<FixedOffset> {
+        resolve_local(&Local, NaiveDateTime::new(self.date, self.day_start)).fixed_offset()
+    }
+
+    pub fn force_active(&mut self) {
+        let now = self.now();
+
+        if self.current_state.active_until.is_none() {
+            self.add_event(Event::active(now));
+        } else {
+            self.set_active_until(Some(now));
+        }
+    }
+
+    pub async fn refresh_active(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.current_state.active_until.is_some() {
+            self.set_active_until(Some(self.now()));
+            self.save().await?;
+        }
+
+        Ok(())
+    }
+
+    pub fn task_elapsed(&self) -> BTreeMap<TaskID, std::time::Duration> {
+        self.current_state
+            .tasks
+            .elapsed_for_date(self.date, self.day_start, self.clock.now())
+    }
+
+    pub fn projection(
+        &self,
+        work_target: std::time::Duration,
+        lunch_target: std::time::Duration,
+    ) -> Projection {
+        let (work_left, lunch_left) = self.elapsed().remaining(work_target, lunch_target);
+
+        let end = if work_left.is_zero() {
+            None
+        } else {
+            chrono::Duration::from_std(work_left + lunch_left)
+                .ok()
+                .map(|left| self.now() + left)
+        };
+
+        Projection {
+            work_left,
+            lunch_left,
+            end,
+        }
+    }
+
+    pub fn elapsed(&self) -> ElapsedSummary {
+        let now = self.clock.now();
+
+        ElapsedSummary {
+            work_time: self
+                .current_state
+                .working
+                .elapsed_for_date(self.date, self.day_start, now),
+            break_time: self.current_state.on_break.elapsed_for_date(
+                self.date,
+                self.day_start,
+                now,
+            ),
+            lunch_time: self.current_state.on_lunch.elapsed_for_date(
+                self.date,
+                self.day_start,
+                now,
+            ),
+            idle_work_time: self.current_state.idle_work.elapsed_for_date(
+                self.date,
+                self.day_start,
+                now,
+            ),
+
+            working: self.current_state.working.active(),
+            on_break: self.current_state.on_break.active(),
+            on_lunch: self.current_state.on_lunch.active(),
+            idle_work: self.current_state.idle_work.active(),
+
+            categories: self
+                .current_state
+                .categories
+                .iter()
+                .map(|(name, category)| {
+                    let elapsed = CategoryElapsed {
+                        time: category
+                            .time
+                            .elapsed_for_date(self.date, self.day_start, now),
+                        active: category.time.active(),
+                        counts_as_work: category.counts_as_work,
+                    };
+
+                    (name.clone(), elapsed)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Versioned for EventLog {
+    const MIGRATIONS: &'static [schema::Migration] = &[schema::unversioned];
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElapsedSummary {
+    pub work_time: std::time::Duration,
+    pub break_time: std::time::Duration,
+    pub lunch_time: std::time::Duration,
+    pub idle_work_time: std::time::Duration,
+
+    pub working: bool,
+    pub on_break: bool,
+    pub on_lunch: bool,
+    pub idle_work: bool,
+
+    pub categories: BTreeMap<String, CategoryElapsed>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryElapsed {
+    pub time: std::time::Duration,
+    pub active: bool,
+    pub counts_as_work: bool,
+}
+
+impl ElapsedSummary {
+    // Lunch and other non-work clocks are tracked as part of working time, but don't count
+    // towards the work target
+    pub fn worked_time(&self) -> std::time::Duration {
+        let non_work: std::time::Duration = self
+            .categories
+            .values()
+            .filter(|category| !category.counts_as_work)
+            .map(|category| category.time)
+            .sum();
+
+        self.work_time
+            .saturating_sub(self.lunch_time)
+            .saturating_sub(non_work)
+    }
+
+    pub fn long_category(
+        &self,
+        name: &str,
+        target: std::time::Duration,
+    ) -> Option<std::time::Duration> {
+        match self.categories.get(name) {
+            Some(category) if category.active && category.time > target => {
+                Some(category.time - target)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn overtime(&self, work_target: std::time::Duration) -> Option<std::time::Duration> {
+        let worked = self.worked_time();
+
+        if self.working && worked > work_target {
+            Some(worked - work_target)
+        } else {
+            None
+        }
+    }
+
+    pub fn long_lunch(&self, lunch_target: std::time::Duration) -> Option<std::time::Duration> {
+        if self.on_lunch && self.lunch_time > lunch_target {
+            Some(self.lunch_time - lunch_target)
+        } else {
+            None
+        }
+    }
+
+    pub fn long_break(&self, break_target: std::time::Duration) -> Option<std::time::Duration> {
+        if self.on_break && self.break_time > break_target {
+            Some(self.break_time - break_target)
+        } else {
+            None
+        }
+    }
+
+    // Work left until the target is met, and lunch left to take before then. Lunch is assumed to
+    // be still to come until one is taken.
+    pub fn remaining(
+        &self,
+        work_target: std::time::Duration,
+        lunch_target: std::time::Duration,
+    ) -> (std::time::Duration, std::time::Duration) {
+        let work_left = work_target.saturating_sub(self.worked_time());
+        let lunch_taken = !self.on_lunch && !self.lunch_time.is_zero();
+
+        let lunch_left = if work_left.is_zero() || lunch_taken {
+            std::time::Duration::ZERO
+        } else {
+            lunch_target.saturating_sub(self.lunch_time)
+        };
+
+        (work_left, lunch_left)
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Projection {
+    pub work_left: std::time::Duration,
+    pub lunch_left: std::time::Duration,
+    // When the target will be met if work carries on from now, unless it's already been met
+    pub end: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all_fields = "camelCase")]
+pub enum Event {
+    ClockIn {
+        time: DateTime<FixedOffset>,
+        clock: ClockType,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    ClockOut {
+        time: DateTime<FixedOffset>,
+        clock: ClockType,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    Active {
+        time: DateTime<FixedOffset>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    Idle {
+        time: DateTime<FixedOffset>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    Tasks {
+        time: DateTime<FixedOffset>,
+        tasks: BTreeSet<TaskID>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        weights: BTreeMap<TaskID, u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    ResolveIdle {
+        time: DateTime<FixedOffset>,
+        span: IdleSpan,
+        resolution: IdleResolution,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    // Time spent on a task away from the computer, logged after the fact
+    ManualTime {
+        time: DateTime<FixedOffset>,
+        task: TaskID,
+        duration: std::time::Duration,
+        counts_as_work: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+}
+
+impl std::cmp::Ord for Event {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let self_time = self.time();
+        let other_time = other.time();
+
+        let time_cmp = self_time.cmp(&other_time);
+        if time_cmp != std::cmp::Ordering::Equal {
+            return time_cmp;
+        }
+
+        self.cmp_contents(other)
+    }
+}
+
+impl std::cmp::PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Notes don't make events different, so they can't hold two places in the log
+impl std::cmp::PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl std::cmp::Eq for Event {}
+
+impl Event {
+    fn cmp_contents(&self, other: &Self) -> std::cmp::Ordering {
+        let self_variant = self.variant_id();
+        let other_variant = other.variant_id();
+
+        let variant_cmp = self_variant.cmp(&other_variant);
+        if variant_cmp != std::cmp::Ordering::Equal {
+            return variant_cmp;
+        }
+
+        match (self, other) {
+            (
+                Event::ClockIn {
+                    clock: self_clock, ..
+                },
+                Event::ClockIn {
+                    clock: other_clock, ..
+                },
+            ) => self_clock.cmp(other_clock),
+            (
+                Event::ClockOut {
+                    clock: self_clock, ..
+                },
+                Event::ClockOut {
+                    clock: other_clock, ..
+                },
+            ) => self_clock.cmp(other_clock),
+            (Event::Idle { .. }, Event::Idle { .. }) => std::cmp::Ordering::Equal,
+            (Event::Active { .. }, Event::Active { .. }) => std::cmp::Ordering::Equal,
+            (
+                Event::Tasks {
+                    tasks: self_tasks,
+                    weights: self_weights,
+                    ..
+                },
+                Event::Tasks {
+                    tasks: other_tasks,
+                    weights: other_weights,
+                    ..
+                },
+            ) => self_tasks
+                .cmp(other_tasks)
+                .then_with(|| self_weights.cmp(other_weights)),
+            (
+                Event::ResolveIdle {
+                    span: self_span,
+                    resolution: self_resolution,
+                    ..
+                },
+                Event::ResolveIdle {
+                    span: other_span,
+                    resolution: other_resolution,
+                    ..
+                },
+            ) => self_span
+                .cmp(other_span)
+                .then_with(|| self_resolution.cmp(other_resolution)),
+            (
+                Event::ManualTime {
+                    task: self_task,
+                    duration: self_duration,
+                    counts_as_work: self_counts_as_work,
+                    ..
+                },
+                Event::ManualTime {
+                    task: other_task,
+                    duration: other_duration,
+                    counts_as_work: other_counts_as_work,
+                    ..
+                },
+            ) => self_task
+                .cmp(other_task)
+                .then_with(|| self_duration.cmp(other_duration))
+                .then_with(|| self_counts_as_work.cmp(other_counts_as_work)),
+            _ => unreachable!(),
+        }
+    }
+
+    // Events coming back from the frontend lose sub-millisecond precision
+    pub fn same_as(&self, other: &Event) -> bool {
+        self.time().timestamp_millis() == other.time().timestamp_millis()
+            && self.cmp_contents(other) == std::cmp::Ordering::Equal
+    }
+
+    pub fn clock_in(time: DateTime<FixedOffset>, clock: ClockType) -> Event {
+        Event::ClockIn {
+            time,
+            clock,
+            note: None,
+        }
+    }
+
+    pub fn clock_out(time: DateTime<FixedOffset>, clock: ClockType) -> Event {
+        Event::ClockOut {
+            time,
+            clock,
+            note: None,
+        }
+    }
+
+    pub fn active(time: DateTime<FixedOffset>) -> Event {
+        Event::Active { time, note: None }
+    }
+
+    pub fn idle(time: DateTime<FixedOffset>) -> Event {
+        Event::Idle { time, note: None }
+    }
+
+    pub fn tasks(
+        time: DateTime<FixedOffset>,
+        tasks: BTreeSet<TaskID>,
+        weights: BTreeMap<TaskID, u32>,
+    ) -> Result<Event, Box<dyn Error>> {
+        let event = Event::Tasks {
+            time,
+            tasks,
+            weights,
+            note: None,
+        };
+        event.validate()?;
+
+        Ok(event)
+    }
+
+    pub fn resolve_idle(
+        time: DateTime<FixedOffset>,
+        span: IdleSpan,
+        resolution: IdleResolution,
+    ) -> Event {
+        Event::ResolveIdle {
+            time,
+            span,
+            resolution,
+            note: None,
+        }
+    }
+
+    pub fn manual_time(
+        time: DateTime<FixedOffset>,
+        task: TaskID,
+        duration: std::time::Duration,
+        counts_as_work: bool,
+    ) -> Result<Event, Box<dyn Error>> {
+        let event = Event::ManualTime {
+            time,
+            task,
+            duration,
+            counts_as_work,
+            note: None,
+        };
+        event.validate()?;
+
+        Ok(event)
+    }
+
+    // Checks what can't be expressed in the event's type, for events built here or sent in from
+    // the frontend
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Event::Tasks { tasks, weights, .. }
+                if !weights.is_empty() && !weights.keys().eq(tasks.iter()) =>
+            {
+                Err("task weights must be given for exactly the tracked tasks".into())
+            }
+            Event::Tasks { weights, .. }
+                if !weights.is_empty() && weights.values().all(|&weight| weight == 0) =>
+            {
+                Err("at least one task weight must be non-zero".into())
+            }
+            Event::ManualTime { duration, .. }
+                if duration.is_zero()
+                    || *duration > std::time::Duration::from_secs(24 * 60 * 60) =>
+            {
+                Err("manual time must be between zero and 24 hours".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn time(&self) -> DateTime<FixedOffset> {
+        match *self {
+            Event::ClockIn { time, .. } => time,
+            Event::ClockOut { time, .. } => time,
+            Event::Active { time, .. } => time,
+            Event::Idle { time, .. } => time,
+            Event::Tasks { time, .. } => time,
+            Event::ResolveIdle { time, .. } => time,
+            Event::ManualTime { time, .. } => time,
+        }
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        match self {
+            Event::ClockIn { note, .. }
+            | Event::ClockOut { note, .. }
+            | Event::Active { note, .. }
+            | Event::Idle { note, .. }
+            | Event::Tasks { note, .. }
+            | Event::ResolveIdle { note, .. }
+            | Event::ManualTime { note, .. } => note.as_deref(),
+        }
+    }
+
+    pub fn with_note(mut self, note: Option<String>) -> Event {
+        match &mut self {
+            Event::ClockIn { note: old, .. }
+            | Event::ClockOut { note: old, .. }
+            | Event::Active { note: old, .. }
+            | Event::Idle { note: old, .. }
+            | Event::Tasks { note: old, .. }
+            | Event::ResolveIdle { note: old, .. }
+            | Event::ManualTime { note: old, .. } => *old = note,
+        }
+
+        self
+    }
+
+    fn variant_id(&self) -> u32 {
         match &self {
             Event::ClockIn { .. } => 0,
             Event::ClockOut { .. } => 1,
             Event::Active { .. } => 2,
             Event::Idle { .. } => 3,
             Event::Tasks { .. } => 4,
+            Event::ResolveIdle { .. } => 5,
+            Event::ManualTime { .. } => 6,
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ClockType {
     Day,
     Break,
     Lunch,
+    Category(Category),
+}
+
+// A user-defined clock, along with the rules for how its time gets counted
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Category {
+    pub name: String,
+    pub counts_as_work: bool,
+    pub pauses_tasks: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::test_util::{hours, local, minutes, new_event_log, task};
+    use std::time::Duration;
+
+    #[test]
+    fn task_time_splits_by_weight() {
+        let start = local(15, 9, 0).fixed_offset();
+        let mut tasks = TrackedMultiTime::default();
+
+        tasks.set_tracked(
+            start,
+            BTreeSet::from([task(1), task(2)]),
+            BTreeMap::from([(task(1), 80), (task(2), 20)]),
+        );
+        tasks.resume(start);
+        tasks.pause(start + chrono::Duration::hours(1));
+
+        assert_eq!(tasks.accumulated.get(&task(1)), Some(&minutes(48)));
+        assert_eq!(tasks.accumulated.get(&task(2)), Some(&minutes(12)));
+    }
+
+    #[test]
+    fn task_time_splits_evenly_without_weights() {
+        let start = local(15, 9, 0).fixed_offset();
+        let mut tasks = TrackedMultiTime::default();
+
+        tasks.set_tracked(start, BTreeSet::from([task(1), task(2)]), BTreeMap::new());
+        tasks.resume(start);
+        tasks.pause(start + chrono::Duration::hours(1));
+
+        assert_eq!(tasks.accumulated.get(&task(1)), Some(&minutes(30)));
+        assert_eq!(tasks.accumulated.get(&task(2)), Some(&minutes(30)));
+    }
+
+    #[test]
+    fn task_elapsed_includes_running_segment() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.add_event(
+            Event::tasks(
+                event_log.now(),
+                BTreeSet::from([task(1), task(2)]),
+                BTreeMap::from([(task(1), 3), (task(2), 1)]),
+            )
+            .unwrap(),
+        );
+
+        clock.set(local(15, 10, 0));
+        event_log.add_event(
+            Event::tasks(event_log.now(), BTreeSet::from([task(2)]), BTreeMap::new()).unwrap(),
+        );
+
+        clock.set(local(15, 10, 30));
+
+        let elapsed = event_log.task_elapsed();
+        assert_eq!(elapsed.get(&task(1)), Some(&minutes(45)));
+        assert_eq!(elapsed.get(&task(2)), Some(&minutes(45)));
+        assert_eq!(
+            event_log.current_state().tasks.accumulated.get(&task(2)),
+            Some(&minutes(15))
+        );
+    }
+
+    #[test]
+    fn task_elapsed_clips_to_log_date() {
+        let clock = FakeClock::new(local(15, 23, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.add_event(
+            Event::tasks(event_log.now(), BTreeSet::from([task(1)]), BTreeMap::new()).unwrap(),
+        );
+
+        clock.set(local(16, 1, 0));
+
+        assert_eq!(event_log.task_elapsed().get(&task(1)), Some(&hours(1)));
+    }
+
+    #[test]
+    fn task_weights_must_match_tasks() {
+        let now = local(15, 9, 0).fixed_offset();
+
+        assert!(Event::tasks(
+            now,
+            BTreeSet::from([task(1), task(2)]),
+            BTreeMap::from([(task(1), 1)])
+        )
+        .is_err());
+        assert!(Event::tasks(
+            now,
+            BTreeSet::from([task(1)]),
+            BTreeMap::from([(task(1), 0)])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn unweighted_tasks_event_deserializes() {
+        let event: Event = serde_json::from_str(
+            r#"{"type":"Tasks","time":"2023-11-15T09:00:00-05:00","tasks":[1,2]}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(event, Event::Tasks { weights, .. } if weights.is_empty()));
+    }
+
+    fn category(name: &str, counts_as_work: bool, pauses_tasks: bool) -> ClockType {
+        ClockType::Category(Category {
+            name: name.to_owned(),
+            counts_as_work,
+            pauses_tasks,
+        })
+    }
+
+    #[test]
+    fn non_work_category_pauses_tasks() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+        let commute = category("Commute", false, true);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.add_event(
+            Event::tasks(event_log.now(), BTreeSet::from([task(1)]), BTreeMap::new()).unwrap(),
+        );
+
+        clock.set(local(15, 10, 0));
+        event_log.add_event(Event::clock_in(event_log.now(), commute.clone()));
+        clock.set(local(15, 10, 30));
+        event_log.add_event(Event::clock_out(event_log.now(), commute));
+        clock.set(local(15, 11, 0));
+
+        let elapsed = event_log.elapsed();
+        assert_eq!(elapsed.work_time, hours(2));
+        assert_eq!(elapsed.worked_time(), hours(1) + minutes(30));
+        assert_eq!(elapsed.categories["Commute"].time, minutes(30));
+        assert_eq!(
+            event_log.task_elapsed().get(&task(1)),
+            Some(&(hours(1) + minutes(30)))
+        );
+    }
+
+    #[test]
+    fn work_category_keeps_tasks_running() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.add_event(
+            Event::tasks(event_log.now(), BTreeSet::from([task(1)]), BTreeMap::new()).unwrap(),
+        );
+
+        clock.set(local(15, 10, 0));
+        event_log.add_event(Event::clock_in(
+            event_log.now(),
+            category("Meeting", true, false),
+        ));
+
+        // Being idle in a meeting isn't idle work
+        clock.set(local(15, 10, 10));
+        event_log.add_event(Event::idle(event_log.now()));
+        clock.set(local(15, 11, 0));
+
+        let elapsed = event_log.elapsed();
+        assert!(!elapsed.idle_work);
+        assert_eq!(elapsed.worked_time(), hours(2));
+        assert_eq!(
+            elapsed.long_category("Meeting", minutes(45)),
+            Some(minutes(15))
+        );
+        assert_eq!(event_log.task_elapsed().get(&task(1)), Some(&hours(2)));
+
+        event_log.add_event(Event::clock_out(event_log.now(), ClockType::Day));
+        assert!(!event_log.elapsed().categories["Meeting"].active);
+    }
+
+    #[test]
+    fn clock_types_deserialize() {
+        let event: Event = serde_json::from_str(
+            r#"{"type":"ClockIn","time":"2023-11-15T09:00:00-05:00","clock":"Break"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            event,
+            Event::ClockIn {
+                clock: ClockType::Break,
+                ..
+            }
+        ));
+
+        let event = Event::clock_in(
+            local(15, 9, 0).fixed_offset(),
+            category("On-call", true, true),
+        );
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+    }
+
+    #[test]
+    fn manual_time_adds_to_task() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 10, 0));
+
+        event_log
+            .add_manual_time(None, task(1), minutes(45), true, Some("call".to_owned()))
+            .unwrap();
+        event_log
+            .add_manual_time(None, task(2), minutes(30), false, None)
+            .unwrap();
+
+        let elapsed = event_log.elapsed();
+        assert_eq!(elapsed.work_time, hours(1) + minutes(45));
+        assert_eq!(event_log.task_elapsed().get(&task(1)), Some(&minutes(45)));
+        assert_eq!(event_log.task_elapsed().get(&task(2)), Some(&minutes(30)));
+        assert_eq!(
+            event_log
+                .events()
+                .iter()
+                .filter_map(Event::note)
+                .collect::<Vec<_>>(),
+            vec!["call"]
+        );
+
+        assert!(event_log
+            .add_manual_time(None, task(1), Duration::ZERO, true, None)
+            .is_err());
+    }
+
+    #[test]
+    fn manual_time_for_past_day() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        clock.set(local(17, 9, 0));
+        event_log
+            .add_manual_time(None, task(1), minutes(45), true, None)
+            .unwrap();
+
+        assert_eq!(
+            event_log.events().first().map(Event::time),
+            Some(local(15, 0, 0).fixed_offset())
+        );
+        assert_eq!(event_log.elapsed().work_time, minutes(45));
+
+        // The same entry again needs its own time
+        assert!(event_log
+            .add_manual_time(None, task(1), minutes(45), true, None)
+            .is_err());
+        event_log
+            .add_manual_time(
+                Some(local(15, 12, 0).fixed_offset()),
+                task(1),
+                minutes(45),
+                true,
+                None,
+            )
+            .unwrap();
+        assert_eq!(event_log.task_elapsed().get(&task(1)), Some(&minutes(90)));
+    }
+
+    #[test]
+    fn elapsed_clips_running_time_to_day_boundaries() {
+        let mut tracked = TrackedTime::default();
+        tracked.start_at(local(15, 22, 0).fixed_offset());
+
+        let now = local(16, 2, 0);
+
+        assert_eq!(
+            tracked.elapsed_for_date(local(15, 0, 0).date_naive(), NaiveTime::MIN, now),
+            hours(2)
+        );
+        assert_eq!(
+            tracked.elapsed_for_date(local(16, 0, 0).date_naive(), NaiveTime::MIN, now),
+            hours(2)
+        );
+        assert_eq!(
+            tracked.elapsed_for_date(local(17, 0, 0).date_naive(), NaiveTime::MIN, now),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn workday_rolls_over_at_day_start() {
+        let day_start = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+
+        assert_eq!(workday_for(&local(16, 0, 30), day_start).day(), 15);
+        assert_eq!(workday_for(&local(16, 3, 59), day_start).day(), 15);
+        assert_eq!(workday_for(&local(16, 4, 0), day_start).day(), 16);
+        assert_eq!(workday_for(&local(16, 0, 30), NaiveTime::MIN).day(), 16);
+    }
+
+    #[test]
+    fn elapsed_clips_to_day_start() {
+        let day_start = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+
+        let mut tracked = TrackedTime::default();
+        tracked.start_at(local(15, 22, 0).fixed_offset());
+
+        let now = local(16, 5, 0);
+
+        assert_eq!(
+            tracked.elapsed_for_date(local(15, 0, 0).date_naive(), day_start, now),
+            hours(6)
+        );
+        assert_eq!(
+            tracked.elapsed_for_date(local(16, 0, 0).date_naive(), day_start, now),
+            hours(1)
+        );
+    }
+
+    #[test]
+    fn elapsed_includes_accumulated_time() {
+        let mut tracked = TrackedTime::default();
+        tracked.start_at(local(15, 8, 0).fixed_offset());
+        tracked.end_at(local(15, 12, 0).fixed_offset());
+        tracked.start_at(local(15, 13, 0).fixed_offset());
+
+        let now = local(15, 14, 30);
+
+        assert_eq!(
+            tracked.elapsed_for_date(now.date_naive(), NaiveTime::MIN, now),
+            hours(5) + minutes(30)
+        );
+    }
+
+    #[test]
+    fn elapsed_across_spring_forward() {
+        let tz = chrono_tz::America::New_York;
+        let day = NaiveDate::from_ymd_opt(2023, 3, 12).unwrap();
+
+        let mut tracked = TrackedTime::default();
+        tracked.start_at(
+            tz.with_ymd_and_hms(2023, 3, 11, 22, 0, 0)
+                .unwrap()
+                .fixed_offset(),
+        );
+
+        let now = tz.with_ymd_and_hms(2023, 3, 12, 12, 0, 0).unwrap();
+
+        // 2:00-3:00 doesn't exist on this day
+        assert_eq!(
+            tracked.elapsed_for_date(day, NaiveTime::MIN, now),
+            hours(11)
+        );
+        assert_eq!(
+            tracked.elapsed_for_date(day.pred_opt().unwrap(), NaiveTime::MIN, now),
+            hours(2)
+        );
+
+        // A day start inside the gap gets pushed forward to 3:30 EDT
+        let day_start = NaiveTime::from_hms_opt(2, 30, 0).unwrap();
+        assert_eq!(
+            tracked.elapsed_for_date(day, day_start, now),
+            hours(8) + minutes(30)
+        );
+    }
+
+    #[test]
+    fn elapsed_across_spring_forward_east_of_utc() {
+        let tz = chrono_tz::Europe::Berlin;
+        let day = NaiveDate::from_ymd_opt(2023, 3, 26).unwrap();
+
+        let mut tracked = TrackedTime::default();
+        tracked.start_at(
+            tz.with_ymd_and_hms(2023, 3, 25, 22, 0, 0)
+                .unwrap()
+                .fixed_offset(),
+        );
+
+        let now = tz.with_ymd_and_hms(2023, 3, 26, 12, 0, 0).unwrap();
+
+        assert_eq!(
+            tracked.elapsed_for_date(day, NaiveTime::MIN, now),
+            hours(11)
+        );
+
+        // A day start inside the gap gets pushed forward to 3:30 CEST
+        let day_start = NaiveTime::from_hms_opt(2, 30, 0).unwrap();
+        assert_eq!(
+            tracked.elapsed_for_date(day, day_start, now),
+            hours(8) + minutes(30)
+        );
+    }
+
+    #[test]
+    fn elapsed_across_fall_back() {
+        let tz = chrono_tz::America::New_York;
+        let day = NaiveDate::from_ymd_opt(2023, 11, 5).unwrap();
+
+        let mut tracked = TrackedTime::default();
+        tracked.start_at(
+            tz.with_ymd_and_hms(2023, 11, 4, 22, 0, 0)
+                .unwrap()
+                .fixed_offset(),
+        );
+
+        let now = tz.with_ymd_and_hms(2023, 11, 5, 12, 0, 0).unwrap();
+
+        // 1:00-2:00 happens twice on this day
+        assert_eq!(
+            tracked.elapsed_for_date(day, NaiveTime::MIN, now),
+            hours(13)
+        );
+
+        // An ambiguous day start resolves to the first 1:30 (EDT)
+        let day_start = NaiveTime::from_hms_opt(1, 30, 0).unwrap();
+        assert_eq!(
+            tracked.elapsed_for_date(day, day_start, now),
+            hours(11) + minutes(30)
+        );
+    }
+
+    #[test]
+    fn elapsed_with_mixed_offsets() {
+        let clock = FakeClock::new(local(15, 18, 0));
+        let mut event_log = new_event_log(&clock);
+
+        let clock_in = DateTime::parse_from_rfc3339("2023-11-15T09:00:00+01:00").unwrap();
+        let clock_out = DateTime::parse_from_rfc3339("2023-11-15T17:00:00+00:00").unwrap();
+
+        event_log.add_event(Event::clock_in(clock_in, ClockType::Day));
+        event_log.add_event(Event::clock_out(clock_out, ClockType::Day));
+
+        assert_eq!(event_log.elapsed().work_time, hours(9));
+    }
+
+    #[test]
+    fn elapsed_for_previous_day_stops_at_midnight() {
+        let clock = FakeClock::new(local(15, 17, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+
+        clock.set(local(16, 9, 0));
+
+        let elapsed = event_log.elapsed();
+        assert_eq!(elapsed.work_time, hours(7));
+        assert!(elapsed.working);
+    }
+
+    #[test]
+    fn infer_idle_waits_five_minutes() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.force_active();
+
+        clock.advance(chrono::Duration::minutes(4));
+        assert!(!event_log.infer_idle());
+        assert!(!event_log.elapsed().idle_work);
+
+        clock.advance(chrono::Duration::minutes(6));
+        assert!(event_log.infer_idle());
+
+        let elapsed = event_log.elapsed();
+        assert!(elapsed.idle_work);
+        assert_eq!(elapsed.idle_work_time, minutes(10));
+        assert_eq!(
+            event_log.events().last(),
+            Some(&Event::idle(local(15, 9, 0).fixed_offset()))
+        );
+    }
+
+    #[test]
+    fn infer_idle_needs_active_time() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        clock.advance(chrono::Duration::hours(1));
+        assert!(!event_log.infer_idle());
+        assert!(event_log.events().is_empty());
+    }
+
+    #[test]
+    fn force_active_ends_idle_work() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.add_event(Event::idle(event_log.now()));
+
+        clock.advance(chrono::Duration::minutes(20));
+        event_log.force_active();
+
+        clock.advance(chrono::Duration::minutes(20));
+
+        let elapsed = event_log.elapsed();
+        assert!(!elapsed.idle_work);
+        assert_eq!(elapsed.idle_work_time, minutes(20));
+        assert_eq!(elapsed.work_time, minutes(40));
+    }
+
+    #[test]
+    fn insert_event_replays_state() {
+        let clock = FakeClock::new(local(15, 8, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 12, 0));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Lunch));
+        clock.set(local(15, 14, 0));
+        assert!(event_log.current_state().on_lunch.active());
+
+        // Forgot to clock out of lunch at 13:00
+        event_log
+            .insert_event(Event::clock_out(
+                local(15, 13, 0).fixed_offset(),
+                ClockType::Lunch,
+            ))
+            .unwrap();
+
+        assert!(!event_log.current_state().on_lunch.active());
+        assert_eq!(event_log.elapsed().lunch_time, hours(1));
+
+        // Nothing can be inserted after the current time
+        assert!(event_log
+            .insert_event(Event::clock_out(
+                local(15, 15, 0).fixed_offset(),
+                ClockType::Day
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn prune_activity_keeps_state() {
+        let clock = FakeClock::new(local(15, 8, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+
+        for minute in [1, 2, 3] {
+            clock.set(local(15, 8, minute));
+            event_log.add_event(Event::active(event_log.now()));
+        }
+
+        clock.set(local(15, 8, 10));
+        event_log.add_event(Event::idle(event_log.now()));
+        clock.set(local(15, 8, 11));
+        event_log.add_event(Event::idle(event_log.now()));
+        clock.set(local(15, 8, 30));
+        event_log.add_event(Event::active(event_log.now()));
+        clock.set(local(15, 8, 31));
+        event_log.add_event(Event::active(event_log.now()).with_note(Some("kept".to_owned())));
+        clock.set(local(15, 9, 0));
+        event_log.add_event(Event::clock_out(event_log.now(), ClockType::Day));
+
+        let elapsed = event_log.elapsed();
+        event_log.prune_activity();
+
+        // The first active and idle events start things, and the last active ends the idle time
+        assert_eq!(event_log.events().len(), 6);
+        assert!(crate::consistency::check(&event_log).is_empty());
+        assert_eq!(event_log.elapsed().work_time, elapsed.work_time);
+        assert_eq!(event_log.elapsed().idle_work_time, elapsed.idle_work_time);
+        assert_eq!(event_log.current_state().pending_idle.len(), 1);
+    }
+
+    #[test]
+    fn amend_event_rolls_back_on_collision() {
+        let clock = FakeClock::new(local(15, 8, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 10, 0));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Break));
+        clock.set(local(15, 10, 15));
+        event_log.add_event(Event::clock_out(event_log.now(), ClockType::Break));
+        clock.set(local(15, 11, 0));
+
+        let break_in = Event::clock_in(local(15, 10, 0).fixed_offset(), ClockType::Break);
+        let break_out = Event::clock_out(local(15, 10, 15).fixed_offset(), ClockType::Break);
+        let events = event_log.events().clone();
+
+        // Turning the break's start into a second copy of its end collides with the end
+        assert!(event_log.amend_event(&break_in, break_out.clone()).is_err());
+        assert!(event_log.events() == &events);
+        assert_eq!(event_log.elapsed().break_time, minutes(15));
+
+        event_log
+            .amend_event(
+                &break_out,
+                Event::clock_out(local(15, 10, 45).fixed_offset(), ClockType::Break),
+            )
+            .unwrap();
+        assert_eq!(event_log.elapsed().break_time, minutes(45));
+    }
+
+    #[test]
+    fn remove_event_needs_existing_event() {
+        let clock = FakeClock::new(local(15, 8, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 9, 0));
+
+        assert!(event_log
+            .remove_event(&Event::clock_out(
+                local(15, 8, 30).fixed_offset(),
+                ClockType::Day
+            ))
+            .is_err());
+        assert!(event_log
+            .remove_event(&Event::clock_in(
+                local(15, 8, 0).fixed_offset(),
+                ClockType::Break
+            ))
+            .is_err());
+        assert_eq!(event_log.events().len(), 1);
+        assert!(event_log.current_state().working.active());
+    }
+
+    #[test]
+    fn events_match_to_the_millisecond() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        let time = local(15, 8, 0).fixed_offset() + chrono::Duration::nanoseconds(123_456_789);
+        event_log.add_event(Event::clock_in(time, ClockType::Day));
+
+        // What comes back after a round trip through a JS Date
+        let millis = time.with_nanosecond(123_000_000).unwrap();
+        let event = Event::clock_in(millis, ClockType::Day);
+        assert!(event.same_as(&Event::clock_in(time, ClockType::Day)));
+
+        let next_milli =
+            Event::clock_in(millis + chrono::Duration::milliseconds(1), ClockType::Day);
+        assert!(!next_milli.same_as(&event));
+        assert!(event_log.remove_event(&next_milli).is_err());
+
+        event_log.remove_event(&event).unwrap();
+        assert!(event_log.events().is_empty());
+        assert!(!event_log.current_state().working.active());
+    }
+
+    #[test]
+    fn edited_events_are_validated() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        let tasks =
+            Event::tasks(event_log.now(), BTreeSet::from([task(1)]), BTreeMap::new()).unwrap();
+        event_log.add_event(tasks.clone());
+        clock.set(local(15, 10, 0));
+
+        let huge = Event::ManualTime {
+            time: local(15, 9, 30).fixed_offset(),
+            task: task(1),
+            duration: Duration::MAX,
+            counts_as_work: true,
+            note: None,
+        };
+        assert!(event_log.insert_event(huge).is_err());
+
+        let mismatched = Event::Tasks {
+            time: local(15, 9, 0).fixed_offset(),
+            tasks: BTreeSet::from([task(1)]),
+            weights: BTreeMap::from([(task(2), 1)]),
+            note: None,
+        };
+        assert!(event_log.amend_event(&tasks, mismatched).is_err());
+
+        assert_eq!(event_log.events().len(), 1);
+        assert!(event_log.events().contains(&tasks));
+    }
+
+    #[test]
+    fn notes_leave_events_equal() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        let clock_in = Event::clock_in(event_log.now(), ClockType::Day);
+        event_log.add_event(clock_in.clone());
+
+        let noted = clock_in.clone().with_note(Some("early start".to_owned()));
+        assert_eq!(noted, clock_in);
+
+        event_log
+            .set_event_note(&clock_in, Some("early start".to_owned()))
+            .unwrap();
+        assert_eq!(event_log.events().len(), 1);
+        assert_eq!(
+            event_log.events().first().and_then(Event::note),
+            Some("early start")
+        );
+
+        // A different note doesn't make it a different event
+        assert!(event_log.insert_event(clock_in.with_note(None)).is_err());
+    }
+
+    // Clocked in at 9:00 on task 1, idle from 10:00 until 10:30, now 11:00
+    fn event_log_with_idle(clock: &Arc<FakeClock>) -> (EventLog, IdleSpan) {
+        let mut event_log = new_event_log(clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.add_event(
+            Event::tasks(event_log.now(), BTreeSet::from([task(1)]), BTreeMap::new()).unwrap(),
+        );
+
+        clock.set(local(15, 10, 0));
+        event_log.add_event(Event::idle(event_log.now()));
+        clock.set(local(15, 10, 30));
+        event_log.add_event(Event::active(event_log.now()));
+        clock.set(local(15, 11, 0));
+
+        let span = IdleSpan {
+            start: local(15, 10, 0).fixed_offset(),
+            end: local(15, 10, 30).fixed_offset(),
+        };
+
+        (event_log, span)
+    }
+
+    #[test]
+    fn active_leaves_pending_idle() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let (event_log, span) = event_log_with_idle(&clock);
+
+        assert_eq!(event_log.current_state().pending_idle, vec![span]);
+        assert_eq!(event_log.elapsed().idle_work_time, minutes(30));
+    }
+
+    #[test]
+    fn resolve_idle_as_work() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let (mut event_log, span) = event_log_with_idle(&clock);
+
+        event_log.resolve_idle(span, IdleResolution::Work).unwrap();
+
+        let elapsed = event_log.elapsed();
+        assert!(event_log.current_state().pending_idle.is_empty());
+        assert_eq!(elapsed.idle_work_time, Duration::ZERO);
+        assert_eq!(elapsed.work_time, hours(2));
+        assert_eq!(event_log.task_elapsed().get(&task(1)), Some(&hours(2)));
+    }
+
+    #[test]
+    fn resolve_idle_as_break() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let (mut event_log, span) = event_log_with_idle(&clock);
+
+        event_log.resolve_idle(span, IdleResolution::Break).unwrap();
+
+        let elapsed = event_log.elapsed();
+        assert_eq!(elapsed.break_time, minutes(30));
+        assert!(!elapsed.on_break);
+        assert_eq!(elapsed.work_time, hours(2));
+        assert_eq!(
+            event_log.task_elapsed().get(&task(1)),
+            Some(&(hours(1) + minutes(30)))
+        );
+    }
+
+    #[test]
+    fn resolve_idle_dropped() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let (mut event_log, span) = event_log_with_idle(&clock);
+
+        // Lunch pauses the task, so its time comes back out of accumulated time instead
+        clock.set(local(15, 11, 30));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Lunch));
+        clock.set(local(15, 12, 0));
+
+        event_log.resolve_idle(span, IdleResolution::Drop).unwrap();
+
+        let elapsed = event_log.elapsed();
+        assert_eq!(elapsed.idle_work_time, Duration::ZERO);
+        assert_eq!(elapsed.worked_time(), hours(2));
+        assert_eq!(event_log.task_elapsed().get(&task(1)), Some(&hours(2)));
+
+        // Replaying the log gives the same result
+        let current_state = event_log.current_state().clone();
+        event_log.replay();
+        assert!(event_log.current_state() == &current_state);
+    }
+
+    #[test]
+    fn resolve_idle_needs_pending_span() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let (mut event_log, span) = event_log_with_idle(&clock);
+
+        event_log.resolve_idle(span, IdleResolution::Lunch).unwrap();
+        assert!(event_log.resolve_idle(span, IdleResolution::Lunch).is_err());
+        assert_eq!(event_log.elapsed().lunch_time, minutes(30));
+    }
+
+    #[test]
+    fn resolve_idle_matches_to_the_millisecond() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+        let nanos = chrono::Duration::nanoseconds(123_456_789);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 10, 0) + nanos);
+        event_log.add_event(Event::idle(event_log.now()));
+        clock.set(local(15, 10, 30) + nanos);
+        event_log.add_event(Event::active(event_log.now()));
+        clock.set(local(15, 11, 0));
+
+        // What comes back after a round trip through a JS Date
+        let pending = event_log.current_state().pending_idle[0];
+        let span: IdleSpan = serde_json::from_value(serde_json::json!({
+            "start": pending.start.to_rfc3339_opts(SecondsFormat::Millis, false),
+            "end": pending.end.to_rfc3339_opts(SecondsFormat::Millis, false),
+        }))
+        .unwrap();
+        assert!(span != pending);
+
+        let next_milli = IdleSpan {
+            start: span.start,
+            end: span.end + chrono::Duration::milliseconds(1),
+        };
+        assert!(event_log
+            .resolve_idle(next_milli, IdleResolution::Break)
+            .is_err());
+
+        event_log.resolve_idle(span, IdleResolution::Break).unwrap();
+        assert!(event_log.current_state().pending_idle.is_empty());
+        assert_eq!(event_log.elapsed().break_time, minutes(30));
+        assert!(event_log
+            .events()
+            .iter()
+            .any(|event| matches!(event, Event::ResolveIdle { span, .. } if *span == pending)));
+    }
+
+    #[test]
+    fn resolving_unknown_idle_changes_nothing() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let (mut event_log, span) = event_log_with_idle(&clock);
+
+        event_log.resolve_idle(span, IdleResolution::Work).unwrap();
+        let elapsed = event_log.elapsed();
+
+        // Edited in by hand or left over after the idle events were amended
+        event_log.add_event(Event::resolve_idle(
+            event_log.now(),
+            span,
+            IdleResolution::Drop,
+        ));
+        event_log.add_event(Event::resolve_idle(
+            event_log.now(),
+            IdleSpan {
+                start: local(15, 9, 30).fixed_offset(),
+                end: local(15, 9, 45).fixed_offset(),
+            },
+            IdleResolution::Lunch,
+        ));
+
+        assert_eq!(event_log.elapsed().work_time, elapsed.work_time);
+        assert_eq!(event_log.elapsed().lunch_time, Duration::ZERO);
+        assert_eq!(event_log.task_elapsed().get(&task(1)), Some(&hours(2)));
+    }
+
+    #[test]
+    fn auto_break_converts_long_idle() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+
+        clock.set(local(15, 10, 0));
+        event_log.add_event(Event::idle(event_log.now()));
+        clock.set(local(15, 10, 20));
+        event_log.add_event(Event::active(event_log.now()));
+        assert!(event_log.auto_break(minutes(15)));
+
+        clock.set(local(15, 11, 0));
+        event_log.add_event(Event::idle(event_log.now()));
+        clock.set(local(15, 11, 5));
+        event_log.add_event(Event::active(event_log.now()));
+        assert!(!event_log.auto_break(minutes(15)));
+
+        let elapsed = event_log.elapsed();
+        assert_eq!(elapsed.break_time, minutes(20));
+        assert_eq!(elapsed.idle_work_time, minutes(5));
+        assert_eq!(event_log.current_state().pending_idle.len(), 1);
+    }
+
+    #[test]
+    fn auto_clock_out_after_idle_limit() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 17, 0));
+        event_log.add_event(Event::idle(event_log.now()));
+
+        clock.set(local(15, 17, 30));
+        assert_eq!(event_log.auto_clock_out(hours(1)), None);
+        assert!(event_log.elapsed().working);
+
+        clock.set(local(15, 18, 30));
+        assert_eq!(
+            event_log.auto_clock_out(hours(1)),
+            Some(local(15, 17, 0).fixed_offset())
+        );
+
+        let elapsed = event_log.elapsed();
+        assert!(!elapsed.working);
+        assert!(!elapsed.idle_work);
+        assert_eq!(elapsed.work_time, hours(8));
+        assert_eq!(elapsed.idle_work_time, Duration::ZERO);
+    }
+
+    #[test]
+    fn auto_clock_out_after_app_closed() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.force_active();
+        clock.set(local(15, 12, 0));
+        event_log.force_active();
+
+        clock.set(local(15, 20, 0));
+        assert!(event_log.infer_idle());
+        assert_eq!(
+            event_log.auto_clock_out(hours(2)),
+            Some(local(15, 12, 0).fixed_offset())
+        );
+        assert_eq!(event_log.elapsed().work_time, hours(3));
+    }
+
+    #[test]
+    fn auto_clock_out_ignores_breaks() {
+        let clock = FakeClock::new(local(15, 9, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Lunch));
+        event_log.add_event(Event::idle(event_log.now()));
+
+        clock.set(local(15, 12, 0));
+        assert_eq!(event_log.auto_clock_out(hours(1)), None);
+    }
+
+    #[test]
+    fn overtime_excludes_lunch() {
+        let clock = FakeClock::new(local(15, 8, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 12, 0));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Lunch));
+        clock.set(local(15, 13, 0));
+        event_log.add_event(Event::clock_out(event_log.now(), ClockType::Lunch));
+
+        clock.set(local(15, 17, 0));
+        assert_eq!(event_log.elapsed().overtime(hours(8)), None);
+
+        clock.set(local(15, 17, 30));
+        assert_eq!(event_log.elapsed().overtime(hours(8)), Some(minutes(30)));
+    }
+
+    #[test]
+    fn projection_leaves_room_for_lunch() {
+        let clock = FakeClock::new(local(15, 8, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 11, 0));
+
+        let projection = event_log.projection(hours(8), hours(1));
+        assert_eq!(projection.work_left, hours(5));
+        assert_eq!(projection.lunch_left, hours(1));
+        assert_eq!(projection.end, Some(local(15, 17, 0).fixed_offset()));
+
+        // Partway through lunch, only the rest of it is left to take
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Lunch));
+        clock.set(local(15, 11, 40));
+
+        let projection = event_log.projection(hours(8), hours(1));
+        assert_eq!(projection.work_left, hours(5));
+        assert_eq!(projection.lunch_left, minutes(20));
+        assert_eq!(projection.end, Some(local(15, 17, 0).fixed_offset()));
+
+        // A short lunch doesn't leave any more lunch to come
+        event_log.add_event(Event::clock_out(event_log.now(), ClockType::Lunch));
+        clock.set(local(15, 12, 0));
+
+        let projection = event_log.projection(hours(8), hours(1));
+        assert_eq!(projection.work_left, minutes(4 * 60 + 40));
+        assert_eq!(projection.lunch_left, Duration::ZERO);
+        assert_eq!(projection.end, Some(local(15, 16, 40).fixed_offset()));
+
+        clock.set(local(15, 17, 0));
+        let projection = event_log.projection(hours(8), hours(1));
+        assert_eq!(projection.work_left, Duration::ZERO);
+        assert_eq!(projection.end, None);
+    }
+
+    #[test]
+    fn overtime_only_while_working() {
+        let clock = FakeClock::new(local(15, 8, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 18, 0));
+        event_log.add_event(Event::clock_out(event_log.now(), ClockType::Day));
+
+        let elapsed = event_log.elapsed();
+        assert_eq!(elapsed.work_time, hours(10));
+        assert_eq!(elapsed.overtime(hours(8)), None);
+    }
+
+    #[test]
+    fn long_lunch_and_break_thresholds() {
+        let clock = FakeClock::new(local(15, 8, 0));
+        let mut event_log = new_event_log(&clock);
+
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Day));
+        clock.set(local(15, 10, 0));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Break));
+
+        clock.set(local(15, 10, 30));
+        assert_eq!(event_log.elapsed().long_break(minutes(30)), None);
+
+        clock.set(local(15, 10, 45));
+        assert_eq!(
+            event_log.elapsed().long_break(minutes(30)),
+            Some(minutes(15))
+        );
+
+        event_log.add_event(Event::clock_out(event_log.now(), ClockType::Break));
+        assert_eq!(event_log.elapsed().long_break(minutes(30)), None);
+
+        clock.set(local(15, 12, 0));
+        event_log.add_event(Event::clock_in(event_log.now(), ClockType::Lunch));
+
+        clock.set(local(15, 13, 10));
+        assert_eq!(event_log.elapsed().long_lunch(hours(1)), Some(minutes(10)));
+    }
 }
@@ -16,7 +16,7 @@ import { listen } from '@tauri-apps/api/event';
 import { useEffect, useState } from 'react';
 
 const zeroTrackedTime: TrackedTime = { since: null, accumulated: 0 };
-const zeroTrackedMultiTime: TrackedMultiTime = { since: null, ids: [], accumulated: {} };
+const zeroTrackedMultiTime: TrackedMultiTime = { since: null, ids: [], weights: {}, accumulated: {} };
 const zeroTimecardState: TimecardState = {
     working: zeroTrackedTime,
     onBreak: zeroTrackedTime,
@@ -24,11 +24,16 @@ const zeroTimecardState: TimecardState = {
     activeUntil: null,
     idleWork: zeroTrackedTime,
     tasks: zeroTrackedMultiTime,
+    pendingIdle: [],
+    categories: {},
 };
 const zeroTimecard: Timecard = {
     initialState: zeroTimecardState,
     currentState: zeroTimecardState,
     events: [],
+    note: null,
+    status: 'Worked',
+    pomodoros: {},
 };
 
 function App() {
@@ -19,23 +19,23 @@ function SingleTimer({
     const now = Date.now();
 
     if (add !== undefined) {
-        for (const { since, accumulated } of add) {
+        for (const { since, accumulated, divider } of add) {
             totalMs += accumulated;
 
             if (since !== null) {
                 anySince = true;
-                totalMs += now - since.getTime();
+                totalMs += (now - since.getTime()) / (divider ?? 1);
             }
         }
     }
 
     if (subtract !== undefined) {
-        for (const { since, accumulated } of subtract) {
+        for (const { since, accumulated, divider } of subtract) {
             totalMs -= accumulated;
 
             if (since !== null) {
                 anySince = true;
-                totalMs -= now - since.getTime();
+                totalMs -= (now - since.getTime()) / (divider ?? 1);
             }
         }
     }
@@ -121,8 +121,9 @@ function TaskList({ tasksTime }: TaskListProps) {
 
     function timeForTask(id: number): TrackedTime | undefined {
         let since = tasksTime.since;
+        const divider = dividerForTask(id);
 
-        if (!tasksTime.ids.includes(id)) {
+        if (!tasksTime.ids.includes(id) || divider === undefined) {
             since = null;
         }
 
@@ -135,10 +136,28 @@ function TaskList({ tasksTime }: TaskListProps) {
         return {
             accumulated: accumulated || 0,
             since,
-            divider: tasksTime.ids.length,
+            divider,
         };
     }
 
+    // Same split as TrackedMultiTime::split, undefined when the task gets no share of the time
+    function dividerForTask(id: number): number | undefined {
+        const weights = Object.values(tasksTime.weights);
+
+        if (weights.length === 0) {
+            return tasksTime.ids.length;
+        }
+
+        const weight = tasksTime.weights[id];
+
+        if (weight === undefined || weight === 0) {
+            return undefined;
+        }
+
+        const totalWeight = weights.reduce((total, weight) => total + weight, 0);
+        return totalWeight / weight;
+    }
+
     const ems = [];
 
     if (newTaskID !== undefined) {
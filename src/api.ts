@@ -1,6 +1,10 @@
 import { invoke } from "@tauri-apps/api";
-import { ClockType, Timecard, parseTimecard } from "./util/timecard";
+import { Duration, msToDuration } from "./util/duration";
+import { CategorySettings, ClockType, DayStatus, IdleResolution, IdleSpan, Projection, Timecard, TimecardDiagnostic, TimecardEvent, parseProjection, parseTimecard } from "./util/timecard";
 import { Recents, Task } from "./util/task";
+import { Schedule, ScheduleException } from "./util/settings";
+import { Pomodoro, RawPomodoro, parsePomodoro } from "./util/pomodoro";
+import { FlexSummary, History, Period, PeriodSummary, RawPeriodSummary, parseFlexSummary, parseHistory, parsePeriodSummary } from "./util/history";
 
 export async function clockIn(clock: ClockType) {
     return await invoke('clock_in', {clock});
@@ -10,14 +14,116 @@ export async function clockOut(clock: ClockType) {
     return await invoke('clock_out', {clock});
 }
 
-export async function setTasks(tasks: number[]) {
-    return await invoke('set_tasks', {tasks});
+export async function setTasks(tasks: number[], weights?: Record<number, number>) {
+    return await invoke('set_tasks', {tasks, weights});
+}
+
+export async function startPomodoro(task: number, focusMs: number, restMs: number) {
+    return await invoke('start_pomodoro', {task, focus: msToDuration(focusMs), rest: msToDuration(restMs)});
+}
+
+export async function stopPomodoro() {
+    return await invoke('stop_pomodoro');
+}
+
+export async function getPomodoro(): Promise<Pomodoro | null> {
+    const raw: RawPomodoro | null = await invoke('get_pomodoro');
+    return raw === null ? null : parsePomodoro(raw);
+}
+
+export async function resolveIdle(span: IdleSpan, resolution: IdleResolution) {
+    return await invoke('resolve_idle', {span, resolution});
+}
+
+export async function insertEvent(event: TimecardEvent) {
+    return await invoke('insert_event', {event});
+}
+
+export async function deleteEvent(event: TimecardEvent) {
+    return await invoke('delete_event', {event});
+}
+
+export async function amendEvent(old: TimecardEvent, newEvent: TimecardEvent) {
+    return await invoke('amend_event', {old, new: newEvent});
+}
+
+export async function setEventNote(event: TimecardEvent, note: string | null) {
+    return await invoke('set_event_note', {event, note});
+}
+
+export async function setDayNote(date: string, note: string | null) {
+    return await invoke('set_day_note', {date, note});
+}
+
+export async function addManualTime(
+    date: string,
+    task: number,
+    ms: number,
+    countsAsWork: boolean,
+    note: string | null,
+    time?: Date,
+) {
+    return await invoke('add_manual_time', {date, time, task, duration: msToDuration(ms), countsAsWork, note});
+}
+
+export async function setDayStatus(date: string, status: DayStatus) {
+    return await invoke('set_day_status', {date, status});
+}
+
+export async function getCategories(): Promise<CategorySettings[]> {
+    return await invoke('get_categories');
+}
+
+export async function getSchedule(): Promise<Schedule> {
+    return await invoke('get_schedule');
+}
+
+export async function setWeeklySchedule(weekly: Duration[]) {
+    return await invoke('set_weekly_schedule', {weekly});
+}
+
+export async function setScheduleException(date: string, exception: ScheduleException | null) {
+    return await invoke('set_schedule_exception', {date, exception});
+}
+
+export interface QuarantinedFile {
+    name: string;
+    size: number;
+}
+
+export async function listQuarantined(): Promise<QuarantinedFile[]> {
+    return await invoke('list_quarantined');
 }
 
 export async function getCurrentTimecard(): Promise<Timecard> {
     return parseTimecard(await invoke('get_current_timecard'));
 }
 
+export async function getTaskElapsed(): Promise<Record<number, Duration>> {
+    return await invoke('get_task_elapsed');
+}
+
+export async function checkTimecard(): Promise<TimecardDiagnostic[]> {
+    return await invoke('check_timecard');
+}
+
+export async function getHistory(start: string, end: string): Promise<History> {
+    return parseHistory(await invoke('get_history', {start, end}));
+}
+
+export async function getPeriodSummaries(period: Period, start: string, end: string): Promise<PeriodSummary[]> {
+    const raw: RawPeriodSummary[] = await invoke('get_period_summaries', {period, start, end});
+    return raw.map(parsePeriodSummary);
+}
+
+export async function getProjection(): Promise<Projection> {
+    return parseProjection(await invoke('get_projection'));
+}
+
+export async function getFlexBalance(): Promise<FlexSummary> {
+    return parseFlexSummary(await invoke('get_flex_balance'));
+}
+
 export async function getRecents(): Promise<Recents> {
     return await invoke('get_recents');
 }
@@ -7,6 +7,15 @@ export function durationToMs(duration: Duration): number {
     return duration.secs * 1000 + duration.nanos / 1_000_000;
 }
 
+export function msToDuration(ms: number): Duration {
+    const secs = Math.floor(ms / 1000);
+
+    return {
+        secs,
+        nanos: Math.round((ms - secs * 1000) * 1_000_000),
+    };
+}
+
 export const msInSecond = 1000;
 export const msInMinute = msInSecond * 60;
 export const msInHour = msInMinute * 60;
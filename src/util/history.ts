@@ -0,0 +1,170 @@
+import { Duration, durationToMs } from './duration';
+import { DayStatus } from './timecard';
+
+export interface RawDaySummary {
+    date: string;
+    workTime: Duration;
+    breakTime: Duration;
+    lunchTime: Duration;
+    idleWorkTime: Duration;
+    working: boolean;
+    onBreak: boolean;
+    onLunch: boolean;
+    idleWork: boolean;
+    tasks: Record<number, Duration>;
+    note: string | null;
+    eventNotes: EventNote[];
+    status: DayStatus;
+    pomodoros: Record<number, number>;
+}
+
+export interface EventNote {
+    time: string;
+    note: string;
+}
+
+export interface DaySummary {
+    date: string;
+    workTime: number;
+    breakTime: number;
+    lunchTime: number;
+    idleWorkTime: number;
+    working: boolean;
+    onBreak: boolean;
+    onLunch: boolean;
+    idleWork: boolean;
+    tasks: Record<number, number>;
+    note: string | null;
+    eventNotes: EventNote[];
+    status: DayStatus;
+    pomodoros: Record<number, number>;
+}
+
+export interface RawHistory {
+    start: string;
+    end: string;
+    days: RawDaySummary[];
+    taskTotals: Record<number, Duration>;
+}
+
+export interface History {
+    start: string;
+    end: string;
+    days: DaySummary[];
+    taskTotals: Record<number, number>;
+}
+
+export function parseHistory(raw: RawHistory): History {
+    return {
+        start: raw.start,
+        end: raw.end,
+        days: raw.days.map(parseDaySummary),
+        taskTotals: parseTaskDurations(raw.taskTotals),
+    };
+}
+
+export function parseDaySummary(raw: RawDaySummary): DaySummary {
+    return {
+        ...raw,
+        workTime: durationToMs(raw.workTime),
+        breakTime: durationToMs(raw.breakTime),
+        lunchTime: durationToMs(raw.lunchTime),
+        idleWorkTime: durationToMs(raw.idleWorkTime),
+        tasks: parseTaskDurations(raw.tasks),
+    };
+}
+
+export function parseTaskDurations(raw: Record<number, Duration>): Record<number, number> {
+    const durations: Record<number, number> = {};
+
+    for (const [id, duration] of Object.entries(raw)) {
+        durations[id as unknown as number] = durationToMs(duration);
+    }
+
+    return durations;
+}
+
+export type Period = 'week' | 'month';
+
+export interface RawPeriodSummary {
+    start: string;
+    end: string;
+    daysLogged: number;
+    workdays: number;
+    workedTime: Duration;
+    breakTime: Duration;
+    lunchTime: Duration;
+    idleWorkTime: Duration;
+    creditedTime: Duration;
+    target: Duration;
+    over: Duration;
+    under: Duration;
+}
+
+export interface PeriodSummary {
+    start: string;
+    end: string;
+    daysLogged: number;
+    workdays: number;
+    workedTime: number;
+    breakTime: number;
+    lunchTime: number;
+    idleWorkTime: number;
+    creditedTime: number;
+    target: number;
+    over: number;
+    under: number;
+}
+
+export function parsePeriodSummary(raw: RawPeriodSummary): PeriodSummary {
+    return {
+        ...raw,
+        workedTime: durationToMs(raw.workedTime),
+        breakTime: durationToMs(raw.breakTime),
+        lunchTime: durationToMs(raw.lunchTime),
+        idleWorkTime: durationToMs(raw.idleWorkTime),
+        creditedTime: durationToMs(raw.creditedTime),
+        target: durationToMs(raw.target),
+        over: durationToMs(raw.over),
+        under: durationToMs(raw.under),
+    };
+}
+
+export interface RawFlexBalance {
+    start: string;
+    end: string;
+    over: Duration;
+    under: Duration;
+}
+
+export interface FlexBalance {
+    start: string;
+    end: string;
+    over: number;
+    under: number;
+}
+
+export interface RawFlexSummary {
+    total: RawFlexBalance;
+    week: RawFlexBalance;
+}
+
+export interface FlexSummary {
+    total: FlexBalance;
+    week: FlexBalance;
+}
+
+export function parseFlexBalance(raw: RawFlexBalance): FlexBalance {
+    return {
+        ...raw,
+        over: durationToMs(raw.over),
+        under: durationToMs(raw.under),
+    };
+}
+
+export function parseFlexSummary(raw: RawFlexSummary): FlexSummary {
+    return {
+        total: parseFlexBalance(raw.total),
+        week: parseFlexBalance(raw.week),
+    };
+}
@@ -0,0 +1,28 @@
+import { Duration, durationToMs } from './duration';
+
+export type PomodoroPhase = 'Focus' | 'Break';
+
+export interface RawPomodoro {
+    task: number;
+    focus: Duration;
+    rest: Duration;
+    phase: PomodoroPhase;
+    phaseEnd: string;
+}
+
+export interface Pomodoro {
+    task: number;
+    focus: number;
+    rest: number;
+    phase: PomodoroPhase;
+    phaseEnd: Date;
+}
+
+export function parsePomodoro(raw: RawPomodoro): Pomodoro {
+    return {
+        ...raw,
+        focus: durationToMs(raw.focus),
+        rest: durationToMs(raw.rest),
+        phaseEnd: new Date(raw.phaseEnd),
+    };
+}
@@ -6,3 +6,17 @@ export interface Settings {
     lunchMax: Duration;
     idleMax: Duration;
 }
+
+export type ExceptionKind = 'holiday' | 'pto' | 'sick' | 'other';
+
+export interface ScheduleException {
+    kind: ExceptionKind;
+    target: Duration;
+    note?: string;
+}
+
+export interface Schedule {
+    // Monday through Sunday
+    weekly: Duration[];
+    exceptions: Record<string, ScheduleException>;
+}
@@ -1,6 +1,30 @@
 import { Duration, durationToMs } from './duration';
 
-export type ClockType = 'Day' | 'Break' | 'Lunch';
+export interface Category {
+    name: string;
+    countsAsWork: boolean;
+    pausesTasks: boolean;
+}
+
+export interface CategorySettings extends Category {
+    target: Duration | null;
+}
+
+export type ClockType = 'Day' | 'Break' | 'Lunch' | { Category: Category };
+
+export type IdleResolution = 'Work' | 'Break' | 'Lunch' | 'Drop';
+
+export type DayStatus = 'Worked' | 'Pto' | 'Sick' | 'Holiday' | { PartialPto: { credited: Duration } };
+
+export interface RawIdleSpan {
+    start: string;
+    end: string;
+}
+
+export interface IdleSpan {
+    start: Date;
+    end: Date;
+}
 
 export interface RawTrackedTime {
     since: string | null;
@@ -13,15 +37,27 @@ export interface TrackedTime {
     accumulated: number;
 }
 
+export interface RawCategoryTime extends RawTrackedTime {
+    countsAsWork: boolean;
+    pausesTasks: boolean;
+}
+
+export interface CategoryTime extends TrackedTime {
+    countsAsWork: boolean;
+    pausesTasks: boolean;
+}
+
 export interface RawTrackedMultiTime {
     since: string | null;
     ids: number[];
+    weights?: Record<number, number>;
     accumulated: Record<number, Duration>;
 }
 
 export interface TrackedMultiTime {
     since: Date | null;
     ids: number[];
+    weights: Record<number, number>;
     accumulated: Record<number, number>;
 }
 
@@ -32,6 +68,8 @@ export interface RawTimecardState {
     idleWork: RawTrackedTime;
     activeUntil: string | null;
     tasks: RawTrackedMultiTime;
+    pendingIdle?: RawIdleSpan[];
+    categories?: Record<string, RawCategoryTime>;
 }
 
 export interface TimecardState {
@@ -41,34 +79,59 @@ export interface TimecardState {
     idleWork: TrackedTime;
     activeUntil: Date | null;
     tasks: TrackedMultiTime;
+    pendingIdle: IdleSpan[];
+    categories: Record<string, CategoryTime>;
 }
 
 export interface RawClockInEvent {
     type: 'ClockIn';
     time: string;
     clock: ClockType;
+    note?: string;
 }
 
 export interface RawClockOutEvent {
     type: 'ClockOut';
     time: string;
     clock: ClockType;
+    note?: string;
 }
 
 export interface RawActiveEvent {
     type: 'Active';
     time: string;
+    note?: string;
 }
 
 export interface RawIdleEvent {
     type: 'Idle';
     time: string;
+    note?: string;
 }
 
 export interface RawTasksEvent {
     type: 'Tasks';
     time: string;
     tasks: number[];
+    weights?: Record<number, number>;
+    note?: string;
+}
+
+export interface RawResolveIdleEvent {
+    type: 'ResolveIdle';
+    time: string;
+    span: RawIdleSpan;
+    resolution: IdleResolution;
+    note?: string;
+}
+
+export interface RawManualTimeEvent {
+    type: 'ManualTime';
+    time: string;
+    task: number;
+    duration: Duration;
+    countsAsWork: boolean;
+    note?: string;
 }
 
 export type RawTimecardEvent =
@@ -76,34 +139,59 @@ export type RawTimecardEvent =
     | RawClockOutEvent
     | RawActiveEvent
     | RawIdleEvent
-    | RawTasksEvent;
+    | RawTasksEvent
+    | RawResolveIdleEvent
+    | RawManualTimeEvent;
 
 export interface ClockInEvent {
     type: 'ClockIn';
     time: Date;
     clock: ClockType;
+    note?: string;
 }
 
 export interface ClockOutEvent {
     type: 'ClockOut';
     time: Date;
     clock: ClockType;
+    note?: string;
 }
 
 export interface ActiveEvent {
     type: 'Active';
     time: Date;
+    note?: string;
 }
 
 export interface IdleEvent {
     type: 'Idle';
     time: Date;
+    note?: string;
 }
 
 export interface TasksEvent {
     type: 'Tasks';
     time: Date;
     tasks: number[];
+    weights?: Record<number, number>;
+    note?: string;
+}
+
+export interface ResolveIdleEvent {
+    type: 'ResolveIdle';
+    time: Date;
+    span: IdleSpan;
+    resolution: IdleResolution;
+    note?: string;
+}
+
+export interface ManualTimeEvent {
+    type: 'ManualTime';
+    time: Date;
+    task: number;
+    duration: Duration;
+    countsAsWork: boolean;
+    note?: string;
 }
 
 export type TimecardEvent =
@@ -111,18 +199,26 @@ export type TimecardEvent =
     | ClockOutEvent
     | ActiveEvent
     | IdleEvent
-    | TasksEvent;
+    | TasksEvent
+    | ResolveIdleEvent
+    | ManualTimeEvent;
 
 export interface RawTimecard {
     initialState: RawTimecardState;
     currentState: RawTimecardState;
     events: RawTimecardEvent[];
+    note?: string;
+    status?: DayStatus;
+    pomodoros?: Record<number, number>;
 }
 
 export interface Timecard {
     initialState: TimecardState;
     currentState: TimecardState;
     events: TimecardEvent[];
+    note: string | null;
+    status: DayStatus;
+    pomodoros: Record<number, number>;
 }
 
 export function parseTimecard(raw: RawTimecard): Timecard {
@@ -130,6 +226,9 @@ export function parseTimecard(raw: RawTimecard): Timecard {
         initialState: parseTimecardState(raw.initialState),
         currentState: parseTimecardState(raw.currentState),
         events: raw.events.map(parseTimecardEvent),
+        note: raw.note ?? null,
+        status: raw.status ?? 'Worked',
+        pomodoros: raw.pomodoros ?? {},
     }
 }
 
@@ -141,6 +240,29 @@ export function parseTimecardState(raw: RawTimecardState): TimecardState {
         activeUntil: raw.activeUntil === null ? null : new Date(raw.activeUntil),
         idleWork: parseTrackedTime(raw.idleWork),
         tasks: parseTrackedMultiTime(raw.tasks),
+        pendingIdle: (raw.pendingIdle ?? []).map(parseIdleSpan),
+        categories: parseCategories(raw.categories ?? {}),
+    }
+}
+
+export function parseCategories(raw: Record<string, RawCategoryTime>): Record<string, CategoryTime> {
+    const categories: Record<string, CategoryTime> = {};
+
+    for (const [name, category] of Object.entries(raw)) {
+        categories[name] = {
+            ...parseTrackedTime(category),
+            countsAsWork: category.countsAsWork,
+            pausesTasks: category.pausesTasks,
+        };
+    }
+
+    return categories;
+}
+
+export function parseIdleSpan(raw: RawIdleSpan): IdleSpan {
+    return {
+        start: new Date(raw.start),
+        end: new Date(raw.end),
     }
 }
 
@@ -161,13 +283,59 @@ export function parseTrackedMultiTime(raw: RawTrackedMultiTime): TrackedMultiTim
     return {
         since: raw.since === null ? null : new Date(raw.since),
         ids: raw.ids,
+        weights: raw.weights ?? {},
         accumulated,
     }
 }
 
 export function parseTimecardEvent(raw: RawTimecardEvent): TimecardEvent {
+    if (raw.type === 'ResolveIdle') {
+        return {
+            ...raw,
+            time: new Date(raw.time),
+            span: parseIdleSpan(raw.span),
+        };
+    }
+
     return {
         ...raw,
         time: new Date(raw.time),
     };
 }
+
+export interface TimecardDiagnostic {
+    type:
+        | 'ClockInWhileClockedIn'
+        | 'ClockOutWithoutClockIn'
+        | 'BreakDuringLunch'
+        | 'LunchDuringBreak'
+        | 'EventOutsideDate'
+        | 'IdleResolvedWithoutSpan'
+        | 'TaskTimeExceedsWorkTime'
+        | 'StateMismatch';
+    time?: string;
+    clock?: ClockType;
+    taskTime?: Duration;
+    workTime?: Duration;
+    field?: string;
+}
+
+export interface RawProjection {
+    workLeft: Duration;
+    lunchLeft: Duration;
+    end: string | null;
+}
+
+export interface Projection {
+    workLeft: number;
+    lunchLeft: number;
+    end: Date | null;
+}
+
+export function parseProjection(raw: RawProjection): Projection {
+    return {
+        workLeft: durationToMs(raw.workLeft),
+        lunchLeft: durationToMs(raw.lunchLeft),
+        end: raw.end === null ? null : new Date(raw.end),
+    };
+}